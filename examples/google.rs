@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use async_executor::Executor;
 use http::{Request, Uri};
-use http_client::HttpClient;
+use http_client::{response_text, HttpClient};
 use simple_error::SimpleResult;
 use smol::MainExecutor;
 
@@ -25,8 +25,10 @@ async fn async_main(_executor: Arc<Executor<'static>>) -> SimpleResult<()> {
     // Get the response
     let mut stream = HttpClient::create_connection(&request).await.expect("connect failed");
     let response = HttpClient::request(&mut stream, &request).await.expect("request failed");
-    let response_body = String::from_utf8(response.body().clone()).expect("failed to parse response body");
     log::info!("response = {response:?}");
+
+    // Take ownership of the body instead of cloning it
+    let response_body = response_text(response).expect("failed to parse response body");
     log::info!("response_body = {response_body}");
 
     Ok(())
@@ -1,21 +1,215 @@
+use std::io;
 use std::net::TcpStream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
 use async_io::Async;
 use async_tls::client::TlsStream;
 use futures_lite::{AsyncRead, AsyncWrite};
 
+use crate::error::HttpClientError;
+
 pub trait AsyncConnection: AsyncRead + AsyncWrite + Send + Sync + Unpin {
     fn is_encrypted(&self) -> bool;
+
+    // Cheap non-blocking check, for use before reusing an idle connection, that the peer hasn't closed it
+    // and hasn't sent anything unsolicited. Transports that can't tell report healthy.
+    fn is_healthy(&self) -> bool {
+        true
+    }
 }
 
 impl AsyncConnection for Async<TcpStream> {
     fn is_encrypted(&self) -> bool {
         false
     }
+
+    fn is_healthy(&self) -> bool {
+        let mut buf = [0u8; 1];
+        match self.get_ref().peek(&mut buf) {
+            Ok(0) => false, // peer closed the connection
+            Ok(_) => false, // data arrived without a request outstanding
+            Err(err) => err.kind() == io::ErrorKind::WouldBlock,
+        }
+    }
 }
 
 impl AsyncConnection for TlsStream<Async<TcpStream>> {
     fn is_encrypted(&self) -> bool {
         true
     }
+
+    // Not overriding is_healthy: TLS 1.3 servers send session tickets after the handshake, so pending
+    // bytes on the socket don't mean the connection is unusable
+}
+
+// async-std's TcpStream, for connections dialed by AsyncConnectionFactory::connect_with_async_std
+#[cfg(feature = "async-std")]
+impl AsyncConnection for async_std::net::TcpStream {
+    fn is_encrypted(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl AsyncConnection for TlsStream<async_std::net::TcpStream> {
+    fn is_encrypted(&self) -> bool {
+        true
+    }
+}
+
+// Adapts any async byte stream (a tunneled socket, a TLS stream from another crate, a test pipe) into an AsyncConnection
+pub struct GenericConnection<S> {
+    stream: S,
+    is_encrypted: bool,
+}
+
+impl<S> GenericConnection<S> {
+    pub fn new(stream: S, is_encrypted: bool) -> Self {
+        Self { stream, is_encrypted }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for GenericConnection<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for GenericConnection<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_close(cx)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Send + Sync + Unpin> AsyncConnection for GenericConnection<S> {
+    fn is_encrypted(&self) -> bool {
+        self.is_encrypted
+    }
+}
+
+// Caps the total bytes read over the life of a connection, across every response on it. Once the cap is used up
+// the connection reports unhealthy so it isn't reused, and reads fail with ConnectionByteLimitExceeded wrapped
+// in an io::Error.
+pub struct ByteLimitedConnection {
+    stream: Box<dyn AsyncConnection>,
+    limit: u64,
+    bytes_read: u64,
+}
+
+impl ByteLimitedConnection {
+    pub fn new(stream: Box<dyn AsyncConnection>, limit: u64) -> Self {
+        Self { stream, limit, bytes_read: 0 }
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+}
+
+impl AsyncRead for ByteLimitedConnection {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let remaining = self.limit - self.bytes_read;
+        if remaining == 0 && !buf.is_empty() {
+            return Poll::Ready(Err(io::Error::other(HttpClientError::ConnectionByteLimitExceeded(self.limit))));
+        }
+        // Never hand out more than the cap allows, so the count can't overshoot it
+        let len = buf.len().min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let poll = Pin::new(&mut self.stream).poll_read(cx, &mut buf[..len]);
+        if let Poll::Ready(Ok(read)) = &poll {
+            self.bytes_read += *read as u64;
+        }
+        poll
+    }
+}
+
+impl AsyncWrite for ByteLimitedConnection {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_close(cx)
+    }
+}
+
+impl AsyncConnection for ByteLimitedConnection {
+    fn is_encrypted(&self) -> bool {
+        self.stream.is_encrypted()
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.bytes_read < self.limit && self.stream.is_healthy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::future;
+    use futures_lite::io::Cursor;
+    use http::Request;
+
+    use super::*;
+    use crate::request_options::RequestOptions;
+    use crate::HttpClient;
+
+    // Replays a canned response and swallows whatever is written
+    struct CannedConnection(Cursor<Vec<u8>>);
+
+    impl AsyncRead for CannedConnection {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.0).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for CannedConnection {
+        fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncConnection for CannedConnection {
+        fn is_encrypted(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn byte_limit_error_can_be_downcast() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello".to_vec();
+        let connection = ByteLimitedConnection::new(Box::new(CannedConnection(Cursor::new(response))), 20);
+        let mut stream: Box<dyn AsyncConnection> = Box::new(connection);
+        let request = Request::get("http://example.com/").body(vec![]).unwrap();
+
+        let err = future::block_on(HttpClient::request_with_options(&mut stream, &request, &RequestOptions::default())).unwrap_err();
+        assert!(matches!(err.downcast_ref::<HttpClientError>(), Some(HttpClientError::ConnectionByteLimitExceeded(20))));
+    }
 }
@@ -1,14 +1,29 @@
-use std::net::ToSocketAddrs;
+use std::io;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
 
 use async_io::Async;
+use async_tls::client::TlsStream;
 use async_tls::TlsConnector;
 use http::Request;
+use rustls::ClientConfig;
 use simple_error::{box_err, SimpleResult};
 
-use crate::async_connection::AsyncConnection;
+use crate::async_connection::{AsyncConnection, ByteLimitedConnection};
+use crate::connect_options::{ConnectOptions, ConnectOverrides};
+use crate::error::{ConnectErrorReason, HttpClientError};
 
 pub struct AsyncConnectionFactory;
 
+const HTTP1_ALPN: &[u8] = b"http/1.1";
+
+// Where a request's connection goes, once overrides and resolution have been applied
+struct ConnectTarget {
+    scheme: String,
+    addrs: Vec<SocketAddr>,
+    server_name: String,
+}
+
 impl AsyncConnectionFactory {
     // Extracts the scheme, host, and port from the request URI
     fn extract_host_from_request<T>(req: &Request<T>) -> SimpleResult<(String, String, u16)> {
@@ -17,12 +32,12 @@ impl AsyncConnectionFactory {
         let scheme = uri.scheme_str().ok_or("No scheme found in URI")?;
 
         let host = authority.host();
-        let port = authority.port_u16().unwrap_or_else(|| match scheme {
+        let port = authority.port_u16().unwrap_or(match scheme {
             "http" => 80,
             "https" => 443,
             "ws" => 80,
             "wss" => 443,
-            _ => return 0,
+            _ => 0,
         });
 
         if port == 0 {
@@ -32,25 +47,178 @@ impl AsyncConnectionFactory {
         Ok((scheme.to_string(), host.to_string(), port))
     }
 
+    // Whether a failed handshake came down to the two sides sharing no protocol version
+    fn is_tls_version_mismatch(err: &io::Error) -> bool {
+        let Some(tls_error) = err.get_ref().and_then(|inner| inner.downcast_ref::<rustls::Error>()) else {
+            return false;
+        };
+        matches!(
+            tls_error,
+            rustls::Error::AlertReceived(rustls::AlertDescription::ProtocolVersion)
+                | rustls::Error::PeerIncompatible(rustls::PeerIncompatible::ServerDoesNotSupportTls12Or13)
+                | rustls::Error::PeerIncompatible(rustls::PeerIncompatible::ServerTlsVersionIsDisabledByOurConfig)
+        )
+    }
+
+    // Tries each address in turn, returning the first connection that succeeds or the last error
+    async fn connect_any(addrs: &[SocketAddr]) -> SimpleResult<Async<TcpStream>> {
+        let mut last_err = None;
+        for addr in addrs {
+            match Async::<TcpStream>::connect(*addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(err) => {
+                    log::debug!("failed to connect to {addr}: {err}");
+                    last_err = Some(HttpClientError::Connect {
+                        addr: *addr,
+                        reason: ConnectErrorReason::from(err.kind()),
+                        source: err,
+                    });
+                }
+            }
+        }
+        match last_err {
+            Some(err) => Err(err.into()),
+            None => Err(box_err!("No address to connect to")),
+        }
+    }
+
+    // Adopts a TCP connection established elsewhere, e.g. handed over by a parent process. It must already be
+    // connected; a socket without a peer is rejected rather than failing on first use.
+    pub fn from_tcp_stream(stream: TcpStream) -> SimpleResult<Async<TcpStream>> {
+        let peer_addr = stream.peer_addr().map_err(|err| format!("Socket is not a connected stream: {err}"))?;
+        if let Some(err) = stream.take_error()? {
+            return Err(format!("Socket connected to {peer_addr} has a pending error: {err}").into());
+        }
+        log::debug!("adopting connection to {peer_addr}");
+        Ok(Async::new(stream)?)
+    }
+
+    // Like from_tcp_stream, then runs the TLS handshake for `server_name` over the adopted connection
+    pub async fn from_tcp_stream_with_tls(stream: TcpStream, server_name: &str, options: &ConnectOptions) -> SimpleResult<Box<dyn AsyncConnection>> {
+        let stream = Self::from_tcp_stream(stream)?;
+        let tls_connector = match &options.tls_client_config {
+            Some(tls_client_config) => TlsConnector::from(tls_client_config.clone()),
+            None => TlsConnector::new(),
+        };
+        match tls_connector.connect(server_name, stream).await {
+            Ok(tls_stream) => Ok(Box::new(tls_stream)),
+            Err(err) if Self::is_tls_version_mismatch(&err) => Err(HttpClientError::TlsVersionNotSupported(server_name.to_string()).into()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     pub async fn connect<T: std::fmt::Debug>(request: &Request<T>) -> SimpleResult<Box<dyn AsyncConnection>> {
+        Self::connect_with_options(request, &ConnectOptions::default()).await
+    }
+
+    // Works out where to connect for the request: its scheme, the addresses to try and the TLS server name
+    fn resolve_target<T: std::fmt::Debug>(request: &Request<T>, options: &ConnectOptions) -> SimpleResult<ConnectTarget> {
         log::debug!("request = {request:02x?}");
 
         // Extract the scheme, host, and port from the request
         let (scheme, host, port) = Self::extract_host_from_request(request)?;
-        let addr = format!("{host}:{port}")
-            .to_socket_addrs()?
-            .next()
-            .ok_or("Failed to resolve host")?;
-        let stream = Async::<std::net::TcpStream>::connect(addr).await?;
-
-        // Optionally add TLS based on the scheme
-        let stream: Box<dyn AsyncConnection> = if scheme == "https" || scheme == "wss" {
-            let tls_connector = TlsConnector::new();
-            Box::new(tls_connector.connect(&host, stream).await?)
+        let overrides = request.extensions().get::<ConnectOverrides>();
+        if let Some(overrides) = overrides {
+            overrides.validate(&scheme, &host)?;
+        }
+        let server_name = overrides.and_then(|overrides| overrides.server_name.clone()).unwrap_or_else(|| host.clone());
+
+        // An address override skips resolution, and the address family filter with it since the caller chose it
+        let addrs = match overrides.and_then(|overrides| overrides.connect_addr) {
+            Some(addr) => vec![addr],
+            None => {
+                let addrs = match &options.dns_cache {
+                    Some(dns_cache) => dns_cache.resolve(&host, port)?,
+                    None => format!("{host}:{port}").to_socket_addrs()?.collect(),
+                };
+                if addrs.is_empty() {
+                    return Err(box_err!("Failed to resolve host"));
+                }
+                let addrs: Vec<SocketAddr> = addrs.into_iter().filter(|addr| options.address_family.allows(addr)).collect();
+                if addrs.is_empty() {
+                    return Err(HttpClientError::NoUsableAddress { host, family: options.address_family }.into());
+                }
+                addrs
+            }
+        };
+
+        Ok(ConnectTarget { scheme, addrs, server_name })
+    }
+
+    // Only HTTP/1.1 is spoken on the connection, so a caller's config may not let the server pick anything else.
+    // async-tls doesn't expose the negotiated protocol, so other ALPN protocols are dropped from the offer instead,
+    // and rustls then refuses a server that selects one that wasn't offered.
+    fn http1_only(tls_client_config: &Arc<ClientConfig>) -> SimpleResult<Arc<ClientConfig>> {
+        let offered = &tls_client_config.alpn_protocols;
+        if offered.iter().all(|protocol| protocol == HTTP1_ALPN) {
+            return Ok(tls_client_config.clone());
+        }
+        if !offered.iter().any(|protocol| protocol == HTTP1_ALPN) {
+            let offered: Vec<_> = offered.iter().map(|protocol| String::from_utf8_lossy(protocol)).collect();
+            return Err(format!("TLS config offers ALPN protocols {offered:?} but not http/1.1").into());
+        }
+        log::debug!("dropping ALPN protocols other than http/1.1 from the TLS config");
+        let mut tls_client_config = ClientConfig::clone(tls_client_config);
+        tls_client_config.alpn_protocols.retain(|protocol| protocol == HTTP1_ALPN);
+        Ok(Arc::new(tls_client_config))
+    }
+
+    // Adds TLS when the scheme calls for it and the read cap when one is configured
+    async fn finish_connection<S>(stream: S, target: ConnectTarget, options: &ConnectOptions) -> SimpleResult<Box<dyn AsyncConnection>>
+    where
+        S: AsyncConnection + 'static,
+        TlsStream<S>: AsyncConnection,
+    {
+        let stream: Box<dyn AsyncConnection> = if target.scheme == "https" || target.scheme == "wss" {
+            let tls_connector = match &options.tls_client_config {
+                Some(tls_client_config) => TlsConnector::from(Self::http1_only(tls_client_config)?),
+                None => TlsConnector::new(),
+            };
+            match tls_connector.connect(&target.server_name, stream).await {
+                Ok(tls_stream) => Box::new(tls_stream),
+                Err(err) if Self::is_tls_version_mismatch(&err) => return Err(HttpClientError::TlsVersionNotSupported(target.server_name).into()),
+                Err(err) => return Err(err.into()),
+            }
         } else {
             Box::new(stream)
         };
 
+        let stream: Box<dyn AsyncConnection> = match options.max_connection_read_bytes {
+            Some(limit) => Box::new(ByteLimitedConnection::new(stream, limit)),
+            None => stream,
+        };
+
         Ok(stream)
     }
+
+    pub async fn connect_with_options<T: std::fmt::Debug>(request: &Request<T>, options: &ConnectOptions) -> SimpleResult<Box<dyn AsyncConnection>> {
+        let target = Self::resolve_target(request, options)?;
+        let stream = Self::connect_any(&target.addrs).await?;
+        Self::finish_connection(stream, target, options).await
+    }
+
+    // Like connect_with_options, but dials with async-std's TcpStream so the connection belongs to an async-std
+    // application's reactor. Framing and TLS are the same.
+    #[cfg(feature = "async-std")]
+    pub async fn connect_with_async_std<T: std::fmt::Debug>(request: &Request<T>, options: &ConnectOptions) -> SimpleResult<Box<dyn AsyncConnection>> {
+        let target = Self::resolve_target(request, options)?;
+        let mut last_err = None;
+        for addr in &target.addrs {
+            match async_std::net::TcpStream::connect(addr).await {
+                Ok(stream) => return Self::finish_connection(stream, target, options).await,
+                Err(err) => {
+                    log::debug!("failed to connect to {addr}: {err}");
+                    last_err = Some(HttpClientError::Connect {
+                        addr: *addr,
+                        reason: ConnectErrorReason::from(err.kind()),
+                        source: err,
+                    });
+                }
+            }
+        }
+        match last_err {
+            Some(err) => Err(err.into()),
+            None => Err(box_err!("No address to connect to")),
+        }
+    }
 }
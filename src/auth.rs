@@ -0,0 +1,261 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use http::{header, HeaderMap, HeaderValue, Request};
+use simple_error::SimpleResult;
+
+use crate::checksum::{base64, hex, md5, sha256};
+use crate::request::is_tchar;
+
+// One challenge from a WWW-Authenticate header. Scheme and parameter names are lowercased.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Challenge {
+    pub scheme: String,
+    pub token68: Option<String>,
+    pub params: Vec<(String, String)>,
+}
+
+impl Challenge {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(param_name, _)| param_name == name).map(|(_, value)| value.as_str())
+    }
+}
+
+// Answers authentication challenges. The client asks each configured provider in turn after a 401, and retries
+// the request once with the first Authorization it gets back.
+pub trait AuthProvider: Send + Sync {
+    // The Authorization header answering `challenge` for `request`, or None when the scheme isn't supported
+    fn authorize(&self, challenge: &Challenge, request: &Request<Vec<u8>>) -> SimpleResult<Option<HeaderValue>>;
+}
+
+struct Lexer<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl Lexer<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(u8) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(&predicate) {
+            self.pos += 1;
+        }
+        String::from_utf8_lossy(&self.input[start..self.pos]).into_owned()
+    }
+
+    fn quoted_string(&mut self) -> String {
+        // Opening quote
+        self.pos += 1;
+        let mut value = Vec::new();
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    if let Some(escaped) = self.peek() {
+                        value.push(escaped);
+                        self.pos += 1;
+                    }
+                }
+                b => value.push(b),
+            }
+        }
+        String::from_utf8_lossy(&value).into_owned()
+    }
+
+    // Whether an auth-param (`name=`) starts here, as opposed to the next challenge's scheme
+    fn at_param(&mut self) -> bool {
+        let start = self.pos;
+        let name = self.take_while(is_tchar);
+        self.skip_whitespace();
+        let at_param = !name.is_empty() && self.peek() == Some(b'=');
+        self.pos = start;
+        at_param
+    }
+}
+
+fn is_token68_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~+/".contains(&b)
+}
+
+// Parses a WWW-Authenticate value, which may hold several comma-separated challenges, each with either a token68
+// or comma-separated parameters (RFC 9110 section 11.6.1)
+pub fn parse_challenges(value: &str) -> Vec<Challenge> {
+    let mut lexer = Lexer { input: value.as_bytes(), pos: 0 };
+    let mut challenges = Vec::new();
+
+    loop {
+        while matches!(lexer.peek(), Some(b' ' | b'\t' | b',')) {
+            lexer.pos += 1;
+        }
+        let scheme = lexer.take_while(is_tchar);
+        if scheme.is_empty() {
+            // Nothing parsable left, or a stray character to skip
+            if lexer.peek().is_none() {
+                return challenges;
+            }
+            lexer.pos += 1;
+            continue;
+        }
+        let mut challenge = Challenge {
+            scheme: scheme.to_ascii_lowercase(),
+            ..Challenge::default()
+        };
+        lexer.skip_whitespace();
+
+        // A token68 is followed only by padding, then the end or the next challenge
+        let start = lexer.pos;
+        let mut token68 = lexer.take_while(is_token68_char);
+        token68.push_str(&lexer.take_while(|b| b == b'='));
+        lexer.skip_whitespace();
+        if !token68.is_empty() && !token68.starts_with('=') && matches!(lexer.peek(), None | Some(b',')) {
+            challenge.token68 = Some(token68);
+            challenges.push(challenge);
+            continue;
+        }
+        lexer.pos = start;
+
+        while lexer.at_param() {
+            let name = lexer.take_while(is_tchar).to_ascii_lowercase();
+            lexer.skip_whitespace();
+            // The '='
+            lexer.pos += 1;
+            lexer.skip_whitespace();
+            let value = if lexer.peek() == Some(b'"') { lexer.quoted_string() } else { lexer.take_while(is_tchar) };
+            challenge.params.push((name, value));
+            lexer.skip_whitespace();
+            if lexer.peek() != Some(b',') {
+                break;
+            }
+            lexer.pos += 1;
+            while matches!(lexer.peek(), Some(b' ' | b'\t' | b',')) {
+                lexer.pos += 1;
+            }
+        }
+        challenges.push(challenge);
+    }
+}
+
+// Every challenge in the response's WWW-Authenticate headers, in order
+pub fn challenges(headers: &HeaderMap<HeaderValue>) -> Vec<Challenge> {
+    headers
+        .get_all(header::WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(parse_challenges)
+        .collect()
+}
+
+// Asks `providers` in turn for an answer to any of the challenges, returning the first one given
+pub fn authorize(providers: &[Arc<dyn AuthProvider>], request: &Request<Vec<u8>>, headers: &HeaderMap<HeaderValue>) -> SimpleResult<Option<HeaderValue>> {
+    for challenge in challenges(headers) {
+        for provider in providers {
+            if let Some(authorization) = provider.authorize(&challenge, request)? {
+                return Ok(Some(authorization));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Basic authentication (RFC 7617)
+pub struct BasicAuth {
+    pub username: String,
+    pub password: String,
+}
+
+impl AuthProvider for BasicAuth {
+    fn authorize(&self, challenge: &Challenge, _request: &Request<Vec<u8>>) -> SimpleResult<Option<HeaderValue>> {
+        if challenge.scheme != "basic" {
+            return Ok(None);
+        }
+        let credentials = base64(format!("{}:{}", self.username, self.password).as_bytes());
+        Ok(Some(HeaderValue::from_str(&format!("Basic {credentials}"))?))
+    }
+}
+
+// Bearer tokens (RFC 6750)
+pub struct BearerAuth(pub String);
+
+impl AuthProvider for BearerAuth {
+    fn authorize(&self, challenge: &Challenge, _request: &Request<Vec<u8>>) -> SimpleResult<Option<HeaderValue>> {
+        if challenge.scheme != "bearer" {
+            return Ok(None);
+        }
+        Ok(Some(HeaderValue::from_str(&format!("Bearer {}", self.0))?))
+    }
+}
+
+// Digest authentication (RFC 7616) with the MD5 and SHA-256 algorithms and qop=auth. Session variants and
+// auth-int aren't supported, so challenges that require them are declined.
+pub struct DigestAuth {
+    pub username: String,
+    pub password: String,
+    nonce_count: AtomicU32,
+}
+
+impl DigestAuth {
+    pub fn new(username: &str, password: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            password: password.to_string(),
+            nonce_count: AtomicU32::new(0),
+        }
+    }
+}
+
+impl AuthProvider for DigestAuth {
+    fn authorize(&self, challenge: &Challenge, request: &Request<Vec<u8>>) -> SimpleResult<Option<HeaderValue>> {
+        if challenge.scheme != "digest" {
+            return Ok(None);
+        }
+        let (Some(realm), Some(nonce)) = (challenge.param("realm"), challenge.param("nonce")) else {
+            return Err("Digest challenge without realm or nonce".into());
+        };
+        let algorithm = challenge.param("algorithm").unwrap_or("MD5");
+        let hash: fn(&[u8]) -> String = match algorithm.to_ascii_uppercase().as_str() {
+            "MD5" => |data| hex(&md5(data)),
+            "SHA-256" => |data| hex(&sha256(data)),
+            _ => return Ok(None),
+        };
+        // Without qop the server speaks the original RFC 2069 digest
+        let qop_auth = match challenge.param("qop") {
+            None => false,
+            Some(qop) if qop.split(',').any(|qop| qop.trim().eq_ignore_ascii_case("auth")) => true,
+            Some(_) => return Ok(None),
+        };
+
+        let uri = request.uri().path_and_query().map_or("/", |path_and_query| path_and_query.as_str());
+        let ha1 = hash(format!("{}:{realm}:{}", self.username, self.password).as_bytes());
+        let ha2 = hash(format!("{}:{uri}", request.method()).as_bytes());
+
+        let mut authorization = format!("Digest username={}, realm={}, nonce={}, uri={}, algorithm={algorithm}", quote(&self.username), quote(realm), quote(nonce), quote(uri));
+        let response = if qop_auth {
+            let nc = format!("{:08x}", self.nonce_count.fetch_add(1, Ordering::Relaxed) + 1);
+            let cnonce = hash(format!("{:?}:{nc}", SystemTime::now()).as_bytes())[..16].to_string();
+            authorization.push_str(&format!(", qop=auth, nc={nc}, cnonce={}", quote(&cnonce)));
+            hash(format!("{ha1}:{nonce}:{nc}:{cnonce}:auth:{ha2}").as_bytes())
+        } else {
+            hash(format!("{ha1}:{nonce}:{ha2}").as_bytes())
+        };
+        authorization.push_str(&format!(", response={}", quote(&response)));
+        if let Some(opaque) = challenge.param("opaque") {
+            authorization.push_str(&format!(", opaque={}", quote(opaque)));
+        }
+        Ok(Some(HeaderValue::from_str(&authorization)?))
+    }
+}
@@ -0,0 +1,135 @@
+use futures_lite::{io::BufReader, AsyncRead, AsyncReadExt};
+use http::{HeaderMap, HeaderValue};
+use simple_error::{box_err, SimpleResult};
+
+use crate::response;
+
+// Largest piece handed out by a single next_chunk call
+const BODY_STREAM_BUFFER_SIZE: usize = 8192;
+
+enum BodyFramingState {
+    Length { remaining: u64 },
+    Chunked { remaining_in_chunk: u64, started: bool },
+    UntilEof,
+    Done,
+}
+
+// Reads a response body incrementally as it arrives instead of buffering all of it
+pub struct BodyStream<S> {
+    reader: BufReader<S>,
+    state: BodyFramingState,
+    max_chunk_line_length: usize,
+    trailers: HeaderMap<HeaderValue>,
+    content_length: Option<u64>,
+    bytes_read: u64,
+}
+
+impl<S: AsyncRead + Unpin> BodyStream<S> {
+    // Picks the framing from the response headers; the reader must be positioned at the start of the body
+    pub fn new(reader: BufReader<S>, headers: &HeaderMap<HeaderValue>, max_chunk_line_length: usize) -> SimpleResult<Self> {
+        let mut content_length = None;
+        let state = if response::is_chunked(headers)? {
+            BodyFramingState::Chunked { remaining_in_chunk: 0, started: false }
+        } else if let Some(length) = response::parse_content_length(headers)? {
+            content_length = Some(length);
+            BodyFramingState::Length { remaining: length }
+        } else {
+            BodyFramingState::UntilEof
+        };
+
+        Ok(Self {
+            reader,
+            state,
+            max_chunk_line_length,
+            trailers: HeaderMap::new(),
+            content_length,
+            bytes_read: 0,
+        })
+    }
+
+    // Returns the next piece of the body, or None once the body is complete
+    pub async fn next_chunk(&mut self) -> SimpleResult<Option<Vec<u8>>> {
+        let chunk = self.read_chunk().await?;
+        if let Some(chunk) = &chunk {
+            self.bytes_read += chunk.len() as u64;
+        }
+        Ok(chunk)
+    }
+
+    async fn read_chunk(&mut self) -> SimpleResult<Option<Vec<u8>>> {
+        let mut buffer = vec![0u8; BODY_STREAM_BUFFER_SIZE];
+
+        loop {
+            match &mut self.state {
+                BodyFramingState::Done => return Ok(None),
+                BodyFramingState::Length { remaining: 0 } => {
+                    self.state = BodyFramingState::Done;
+                }
+                BodyFramingState::Length { remaining } => {
+                    let len = (*remaining).min(buffer.len() as u64) as usize;
+                    let read = self.reader.read(&mut buffer[..len]).await?;
+                    if read == 0 {
+                        return Err(box_err!("Connection closed before the end of the response body"));
+                    }
+                    *remaining -= read as u64;
+                    buffer.truncate(read);
+                    return Ok(Some(buffer));
+                }
+                BodyFramingState::Chunked { remaining_in_chunk: 0, started } => {
+                    if *started {
+                        let mut crlf = [0; 2];
+                        self.reader.read_exact(&mut crlf).await?;
+                        if &crlf != b"\r\n" {
+                            return Err(box_err!("Invalid chunked encoding: missing CRLF"));
+                        }
+                    }
+                    let chunk_size = response::read_chunk_size(&mut self.reader, self.max_chunk_line_length).await?;
+                    if chunk_size == 0 {
+                        self.trailers = response::read_trailers(&mut self.reader, self.max_chunk_line_length).await?;
+                        self.state = BodyFramingState::Done;
+                    } else {
+                        self.state = BodyFramingState::Chunked { remaining_in_chunk: chunk_size, started: true };
+                    }
+                }
+                BodyFramingState::Chunked { remaining_in_chunk, .. } => {
+                    let len = (*remaining_in_chunk).min(buffer.len() as u64) as usize;
+                    let read = self.reader.read(&mut buffer[..len]).await?;
+                    if read == 0 {
+                        return Err(box_err!("Connection closed in the middle of a chunk"));
+                    }
+                    *remaining_in_chunk -= read as u64;
+                    buffer.truncate(read);
+                    return Ok(Some(buffer));
+                }
+                BodyFramingState::UntilEof => {
+                    let read = self.reader.read(&mut buffer).await?;
+                    if read == 0 {
+                        self.state = BodyFramingState::Done;
+                    } else {
+                        buffer.truncate(read);
+                        return Ok(Some(buffer));
+                    }
+                }
+            }
+        }
+    }
+
+    // Body bytes handed out so far
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    // Total body length when the server declared it with Content-Length
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    // Trailer fields sent after a chunked body, available once the body is complete
+    pub fn trailers(&self) -> &HeaderMap<HeaderValue> {
+        &self.trailers
+    }
+
+    pub fn into_inner(self) -> BufReader<S> {
+        self.reader
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use event_listener::Event;
+
+struct CancellationState {
+    cancelled: AtomicBool,
+    event: Event,
+}
+
+// Shared signal telling in-flight work to stop. Clones observe the same cancellation.
+#[derive(Clone)]
+pub struct CancellationToken {
+    state: Arc<CancellationState>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(CancellationState {
+                cancelled: AtomicBool::new(false),
+                event: Event::new(),
+            }),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+        self.state.event.notify(usize::MAX);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+
+    // Completes once the token is cancelled
+    pub async fn cancelled(&self) {
+        loop {
+            if self.is_cancelled() {
+                return;
+            }
+            let listener = self.state.event.listen();
+            // re-check after registering so a cancel in between isn't missed
+            if self.is_cancelled() {
+                return;
+            }
+            listener.await;
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use http::{HeaderName, HeaderValue};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use simple_error::SimpleResult;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Md5,
+    Sha256,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumEncoding {
+    // Padded standard base64, as Content-MD5 uses
+    Base64,
+    // Lowercase hex, as x-amz-content-sha256 uses
+    Hex,
+}
+
+// A digest of the request body sent in a header, computed from the body bytes just before the request is written
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BodyChecksum {
+    pub algorithm: ChecksumAlgorithm,
+    pub encoding: ChecksumEncoding,
+    pub header_name: HeaderName,
+}
+
+impl BodyChecksum {
+    // Content-MD5 (RFC 1864)
+    pub fn content_md5() -> Self {
+        Self {
+            algorithm: ChecksumAlgorithm::Md5,
+            encoding: ChecksumEncoding::Base64,
+            header_name: HeaderName::from_static("content-md5"),
+        }
+    }
+
+    // x-amz-content-sha256, as S3 and SigV4 expect
+    pub fn amz_content_sha256() -> Self {
+        Self {
+            algorithm: ChecksumAlgorithm::Sha256,
+            encoding: ChecksumEncoding::Hex,
+            header_name: HeaderName::from_static("x-amz-content-sha256"),
+        }
+    }
+
+    // The header value for `body`. Also usable for a trailer when the body is streamed.
+    pub fn compute(&self, body: &[u8]) -> SimpleResult<HeaderValue> {
+        let digest = match self.algorithm {
+            ChecksumAlgorithm::Md5 => md5(body).to_vec(),
+            ChecksumAlgorithm::Sha256 => sha256(body).to_vec(),
+        };
+        let encoded = match self.encoding {
+            ChecksumEncoding::Base64 => base64(&digest),
+            ChecksumEncoding::Hex => hex(&digest),
+        };
+        Ok(HeaderValue::from_str(&encoded)?)
+    }
+}
+
+// Padded standard base64 (RFC 4648 section 4)
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    encode_base64(bytes, b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/", true)
+}
+
+// Unpadded base64url (RFC 4648 section 5)
+pub(crate) fn base64url(bytes: &[u8]) -> String {
+    encode_base64(bytes, b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_", false)
+}
+
+fn encode_base64(bytes: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let n = group.iter().enumerate().fold(0u32, |n, (i, &b)| n | (u32::from(b) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= group.len() {
+                encoded.push(alphabet[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else if pad {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// Lowercase hex
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// MD5 (RFC 1321). Broken for security, but still what Content-MD5 calls for.
+pub(crate) fn md5(message: &[u8]) -> [u8; 16] {
+    Md5::digest(message).into()
+}
+
+// SHA-256 (FIPS 180-4)
+pub(crate) fn sha256(message: &[u8]) -> [u8; 32] {
+    Sha256::digest(message).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn md5_rfc1321_test_suite() {
+        let vectors: [(&[u8], &str); 7] = [
+            (b"", "d41d8cd98f00b204e9800998ecf8427e"),
+            (b"a", "0cc175b9c0f1b6a831c399e269772661"),
+            (b"abc", "900150983cd24fb0d6963f7d28e17f72"),
+            (b"message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            (b"abcdefghijklmnopqrstuvwxyz", "c3fcd3d76192e4007dfb496cca67e13b"),
+            (b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789", "d174ab98d277d9f5a5611c2c9f419d9f"),
+            (
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
+        ];
+        for (message, digest) in vectors {
+            assert_eq!(hex(&md5(message)), digest);
+        }
+    }
+
+    #[test]
+    fn sha256_fips180_examples() {
+        let vectors: [(&[u8], &str); 3] = [
+            (b"", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+            (b"abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            (
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+        ];
+        for (message, digest) in vectors {
+            assert_eq!(hex(&sha256(message)), digest);
+        }
+    }
+
+    #[test]
+    fn base64_rfc4648_test_vectors() {
+        let vectors = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+        for (input, encoded) in vectors {
+            assert_eq!(base64(input.as_bytes()), encoded);
+            assert_eq!(base64url(input.as_bytes()), encoded.trim_end_matches('='));
+        }
+        assert_eq!(base64url(&[0xfb, 0xff]), "-_8");
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use http::{Response, Uri};
+use simple_error::SimpleResult;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::HttpClientError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    // Requests flow normally
+    Closed,
+    // Requests fail fast with CircuitOpen until the cooldown passes
+    Open,
+    // The cooldown passed and a single probe request is let through to test recovery
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct OriginCircuit {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    // When the half-open probe was let through. A probe that never reported back (its future was dropped)
+    // stops blocking new probes after another cooldown.
+    probe_started_at: Option<Instant>,
+}
+
+// Stops sending requests to an origin after `failure_threshold` consecutive failures, for `cooldown`. Connection
+// errors, timeouts and 5xx responses count as failures; 4xx responses don't, since they are the caller's fault.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    circuits: Mutex<HashMap<String, OriginCircuit>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            circuits: Mutex::new(HashMap::new()),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    // Times cooldowns on `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn elapsed_since(&self, instant: Instant) -> Duration {
+        self.clock.now().saturating_duration_since(instant)
+    }
+
+    // Key circuits are tracked under, e.g. `https://example.com:8443`
+    pub fn origin(uri: &Uri) -> String {
+        format!("{}://{}", uri.scheme_str().unwrap_or("http"), uri.authority().map_or("", |authority| authority.as_str()))
+    }
+
+    // Current state of the circuit for `origin`, for monitoring
+    pub fn state(&self, origin: &str) -> CircuitState {
+        let circuits = self.circuits.lock().unwrap();
+        match circuits.get(origin).and_then(|circuit| circuit.opened_at) {
+            None => CircuitState::Closed,
+            Some(opened_at) if self.elapsed_since(opened_at) < self.cooldown => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    // Admits a request to `origin`, or fails with CircuitOpen while the circuit is open
+    pub(crate) fn acquire(&self, origin: &str) -> SimpleResult<()> {
+        let mut circuits = self.circuits.lock().unwrap();
+        let Some(circuit) = circuits.get_mut(origin) else {
+            return Ok(());
+        };
+        match circuit.opened_at {
+            None => Ok(()),
+            Some(opened_at) if self.elapsed_since(opened_at) >= self.cooldown && circuit.probe_started_at.is_none_or(|started_at| self.elapsed_since(started_at) >= self.cooldown) => {
+                log::debug!("circuit for {origin} half-open, sending a probe");
+                circuit.probe_started_at = Some(self.clock.now());
+                Ok(())
+            }
+            Some(_) => Err(HttpClientError::CircuitOpen(origin.to_string()).into()),
+        }
+    }
+
+    // Records the outcome of a request admitted by `acquire`
+    pub(crate) fn record<T>(&self, origin: &str, result: &SimpleResult<Response<T>>) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let failed = match result {
+            Ok(response) => response.status().is_server_error(),
+            // Requests abandoned by shutdown or turned away by the connection limit say nothing about the origin, but
+            // free up the probe slot
+            Err(err) if matches!(err.downcast_ref::<HttpClientError>(), Some(HttpClientError::Cancelled | HttpClientError::ConnectionLimitReached(_))) => {
+                if let Some(circuit) = circuits.get_mut(origin) {
+                    circuit.probe_started_at = None;
+                }
+                return;
+            }
+            Err(_) => true,
+        };
+
+        if !failed {
+            if circuits.remove(origin).is_some_and(|circuit| circuit.opened_at.is_some()) {
+                log::info!("circuit for {origin} closed");
+            }
+            return;
+        }
+
+        let circuit = circuits.entry(origin.to_string()).or_default();
+        circuit.consecutive_failures += 1;
+        circuit.probe_started_at = None;
+        // A failed probe reopens the circuit for another cooldown
+        if circuit.opened_at.is_some() || circuit.consecutive_failures >= self.failure_threshold {
+            log::warn!("circuit for {origin} opened after {} consecutive failures", circuit.consecutive_failures);
+            circuit.opened_at = Some(self.clock.now());
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_io::Timer;
+use event_listener::Event;
+
+pub type SleepFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+// Where time-dependent features (deadlines, the circuit breaker cooldown, DNS cache expiry) get the time from, so
+// tests can swap the wall clock for a MockClock they advance by hand
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+
+    fn sleep(&self, duration: Duration) -> SleepFuture<'_>;
+}
+
+// The real clock, sleeping on async-io timers
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> SleepFuture<'_> {
+        Box::pin(async move {
+            Timer::after(duration).await;
+        })
+    }
+}
+
+// A clock that only moves when `advance` is called. Sleepers wake once it has been advanced past their deadline.
+#[derive(Debug)]
+pub struct MockClock {
+    start: Instant,
+    elapsed: Mutex<Duration>,
+    advanced: Event,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+            advanced: Event::new(),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+        self.advanced.notify(usize::MAX);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) -> SleepFuture<'_> {
+        let wake_at = self.now() + duration;
+        Box::pin(async move {
+            loop {
+                if self.now() >= wake_at {
+                    return;
+                }
+                let listener = self.advanced.listen();
+                // re-check after registering so an advance in between isn't missed
+                if self.now() >= wake_at {
+                    return;
+                }
+                listener.await;
+            }
+        })
+    }
+}
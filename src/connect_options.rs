@@ -0,0 +1,85 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use http::HeaderValue;
+use rustls::{ClientConfig, ServerName};
+use simple_error::SimpleResult;
+
+use crate::dns_cache::DnsCache;
+
+// Which IP versions connections may use, for networks where one of them is broken
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AddressFamily {
+    #[default]
+    Any,
+    Ipv4Only,
+    Ipv6Only,
+}
+
+impl AddressFamily {
+    pub fn allows(&self, addr: &SocketAddr) -> bool {
+        match self {
+            AddressFamily::Any => true,
+            AddressFamily::Ipv4Only => addr.is_ipv4(),
+            AddressFamily::Ipv6Only => addr.is_ipv6(),
+        }
+    }
+}
+
+impl fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressFamily::Any => write!(f, "any"),
+            AddressFamily::Ipv4Only => write!(f, "IPv4"),
+            AddressFamily::Ipv6Only => write!(f, "IPv6"),
+        }
+    }
+}
+
+// Knobs applied by AsyncConnectionFactory when establishing a connection
+#[derive(Clone, Default)]
+pub struct ConnectOptions {
+    pub dns_cache: Option<Arc<DnsCache>>,
+    // rustls configuration for https/wss connections, async-tls defaults when unset
+    pub tls_client_config: Option<Arc<ClientConfig>>,
+    // Resolved addresses outside this family are skipped
+    pub address_family: AddressFamily,
+    // Total bytes a connection may read over its lifetime before further reads fail, unlimited when unset
+    pub max_connection_read_bytes: Option<u64>,
+}
+
+// Request extension that routes a request somewhere other than its URI says, e.g. connecting to a canary's IP while
+// sending the production Host and SNI. Each field that is unset is derived from the URI as usual.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectOverrides {
+    // Address to connect to instead of resolving the URI host
+    pub connect_addr: Option<SocketAddr>,
+    // Host header to send instead of the URI authority. A Host header set on the request still wins.
+    pub host_header: Option<HeaderValue>,
+    // Server name for SNI and certificate verification instead of the URI host
+    pub server_name: Option<String>,
+}
+
+impl ConnectOverrides {
+    // Checks the overrides make sense for a request with this scheme and URI host
+    pub fn validate(&self, scheme: &str, uri_host: &str) -> SimpleResult<()> {
+        let is_tls = scheme == "https" || scheme == "wss";
+        match &self.server_name {
+            Some(_) if !is_tls => return Err(format!("Server name override on a {scheme} request, which doesn't use TLS").into()),
+            Some(server_name) => {
+                ServerName::try_from(server_name.as_str()).map_err(|_| format!("Invalid server name override: {server_name:?}"))?;
+            }
+            // The URI host is used for SNI, and connecting to an address of our choosing doesn't change that,
+            // but it has to be something a certificate can be checked against
+            None if is_tls && ServerName::try_from(uri_host).is_err() => {
+                return Err(format!("TLS request to {uri_host:?} needs a server name override").into());
+            }
+            None => {}
+        }
+        if self.host_header.as_ref().is_some_and(|host| host.is_empty()) {
+            return Err("Empty Host header override".into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use event_listener::Event;
+use simple_error::SimpleResult;
+
+use crate::error::HttpClientError;
+
+// Caps how many connections a client has open to each origin at once. Requests over the cap wait for a slot, or
+// fail with ConnectionLimitReached when `fail_fast` is set.
+#[derive(Debug)]
+pub struct ConnectionLimiter {
+    max_per_origin: usize,
+    fail_fast: bool,
+    in_use: Mutex<HashMap<String, usize>>,
+    released: Event,
+}
+
+// A connection slot held for one origin, given back when dropped
+pub(crate) struct ConnectionPermit<'a> {
+    limiter: &'a ConnectionLimiter,
+    origin: String,
+}
+
+impl Drop for ConnectionPermit<'_> {
+    fn drop(&mut self) {
+        let mut in_use = self.limiter.in_use.lock().unwrap();
+        if let Some(count) = in_use.get_mut(&self.origin) {
+            *count -= 1;
+            if *count == 0 {
+                in_use.remove(&self.origin);
+            }
+        }
+        drop(in_use);
+        self.limiter.released.notify(usize::MAX);
+    }
+}
+
+impl ConnectionLimiter {
+    pub fn new(max_per_origin: usize, fail_fast: bool) -> Self {
+        Self {
+            max_per_origin: max_per_origin.max(1),
+            fail_fast,
+            in_use: Mutex::new(HashMap::new()),
+            released: Event::new(),
+        }
+    }
+
+    // Connections currently open to `origin`, for monitoring
+    pub fn in_use(&self, origin: &str) -> usize {
+        self.in_use.lock().unwrap().get(origin).copied().unwrap_or(0)
+    }
+
+    fn try_acquire(&self, origin: &str) -> Option<ConnectionPermit<'_>> {
+        let mut in_use = self.in_use.lock().unwrap();
+        let count = in_use.entry(origin.to_string()).or_insert(0);
+        if *count >= self.max_per_origin {
+            return None;
+        }
+        *count += 1;
+        Some(ConnectionPermit {
+            limiter: self,
+            origin: origin.to_string(),
+        })
+    }
+
+    // Takes a connection slot for `origin`, waiting for one to be released if they are all taken
+    pub(crate) async fn acquire(&self, origin: &str) -> SimpleResult<ConnectionPermit<'_>> {
+        loop {
+            if let Some(permit) = self.try_acquire(origin) {
+                return Ok(permit);
+            }
+            if self.fail_fast {
+                return Err(HttpClientError::ConnectionLimitReached(origin.to_string()).into());
+            }
+            let listener = self.released.listen();
+            // re-check after registering so a release in between isn't missed
+            if let Some(permit) = self.try_acquire(origin) {
+                return Ok(permit);
+            }
+            log::debug!("waiting for a connection slot to {origin}");
+            listener.await;
+        }
+    }
+}
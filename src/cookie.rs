@@ -0,0 +1,150 @@
+use std::time::SystemTime;
+
+use http::{header, HeaderMap, HeaderValue};
+use simple_error::{box_err, SimpleResult};
+
+use crate::http_date;
+use crate::request::is_tchar;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+// A cookie set by a Set-Cookie header, with the attributes it was set with
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub domain: Option<String>,
+    pub path: Option<String>,
+    pub expires: Option<SystemTime>,
+    // Seconds until expiry; zero or negative means the cookie is already expired
+    pub max_age: Option<i64>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+}
+
+// Cookie names are RFC 7230 tokens
+fn is_valid_cookie_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(is_tchar)
+}
+
+// Cookie values are RFC 6265 cookie-octets: no controls, whitespace, DQUOTE, comma, semicolon or backslash
+fn is_valid_cookie_value(value: &str) -> bool {
+    value.bytes().all(|b| matches!(b, 0x21 | 0x23..=0x2b | 0x2d..=0x3a | 0x3c..=0x5b | 0x5d..=0x7e))
+}
+
+// Formats caller-managed cookies into a Cookie header value (`name1=val1; name2=val2`)
+pub fn cookies(pairs: &[(&str, &str)]) -> SimpleResult<HeaderValue> {
+    let mut cookie_header = String::new();
+
+    for (name, value) in pairs {
+        if !is_valid_cookie_name(name) {
+            return Err(format!("Invalid cookie name: {name:?}").into());
+        }
+        if !is_valid_cookie_value(value) {
+            return Err(format!("Invalid cookie value for {name}").into());
+        }
+        if !cookie_header.is_empty() {
+            cookie_header.push_str("; ");
+        }
+        cookie_header.push_str(name);
+        cookie_header.push('=');
+        cookie_header.push_str(value);
+    }
+
+    if cookie_header.is_empty() {
+        return Err(box_err!("No cookies provided"));
+    }
+
+    Ok(HeaderValue::from_str(&cookie_header)?)
+}
+
+// Extracts the name=value pair from a Set-Cookie header value, ignoring its attributes
+pub fn parse_set_cookie_pair(set_cookie: &HeaderValue) -> Option<(String, String)> {
+    let set_cookie = set_cookie.to_str().ok()?;
+    let pair = set_cookie.split(';').next()?;
+    let (name, value) = pair.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    Some((name.to_string(), value.to_string()))
+}
+
+// Parses a Set-Cookie header value with its attributes. Unknown attributes and attributes with unusable values are
+// ignored, and when one is repeated the last occurrence wins (RFC 6265 section 5.2).
+pub fn parse_set_cookie(set_cookie: &HeaderValue) -> Option<Cookie> {
+    let (name, value) = parse_set_cookie_pair(set_cookie)?;
+    let mut cookie = Cookie {
+        name,
+        value,
+        ..Cookie::default()
+    };
+
+    let set_cookie = set_cookie.to_str().ok()?;
+    for attribute in set_cookie.split(';').skip(1) {
+        let (attribute_name, attribute_value) = match attribute.split_once('=') {
+            Some((attribute_name, attribute_value)) => (attribute_name.trim(), attribute_value.trim()),
+            None => (attribute.trim(), ""),
+        };
+        match attribute_name.to_ascii_lowercase().as_str() {
+            "domain" if !attribute_value.is_empty() => {
+                // A leading dot is ignored
+                let domain = attribute_value.strip_prefix('.').unwrap_or(attribute_value);
+                cookie.domain = Some(domain.to_ascii_lowercase());
+            }
+            "path" if attribute_value.starts_with('/') => cookie.path = Some(attribute_value.to_string()),
+            "expires" => {
+                if let Some(expires) = http_date::parse_cookie_date(attribute_value) {
+                    cookie.expires = Some(expires);
+                }
+            }
+            "max-age" => {
+                let is_number = attribute_value.strip_prefix('-').unwrap_or(attribute_value).bytes().all(|b| b.is_ascii_digit());
+                if is_number {
+                    if let Ok(max_age) = attribute_value.parse() {
+                        cookie.max_age = Some(max_age);
+                    }
+                }
+            }
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            "samesite" => {
+                cookie.same_site = match attribute_value.to_ascii_lowercase().as_str() {
+                    "strict" => Some(SameSite::Strict),
+                    "lax" => Some(SameSite::Lax),
+                    "none" => Some(SameSite::None),
+                    _ => cookie.same_site,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(cookie)
+}
+
+// Parses every Set-Cookie header in the map, skipping malformed ones with a warning
+pub fn parse_set_cookies(headers: &HeaderMap<HeaderValue>) -> Vec<Cookie> {
+    headers
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|set_cookie| {
+            let cookie = parse_set_cookie(set_cookie);
+            if cookie.is_none() {
+                log::warn!("Skipping malformed Set-Cookie: {set_cookie:?}");
+            }
+            cookie
+        })
+        .collect()
+}
@@ -0,0 +1,49 @@
+use http::{HeaderMap, HeaderValue};
+
+// Access-Control-Allow-* headers returned by a CORS preflight response
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CorsPolicy {
+    pub allow_origin: Option<String>,
+    pub allow_methods: Vec<String>,
+    pub allow_headers: Vec<String>,
+    pub expose_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age: Option<u64>,
+}
+
+// Splits a comma separated header list into its trimmed, non-empty items
+fn header_list(headers: &HeaderMap<HeaderValue>, name: &str) -> Vec<String> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+impl CorsPolicy {
+    pub fn from_headers(headers: &HeaderMap<HeaderValue>) -> Self {
+        let header_str = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::trim);
+
+        Self {
+            allow_origin: header_str("access-control-allow-origin").map(str::to_string),
+            allow_methods: header_list(headers, "access-control-allow-methods"),
+            allow_headers: header_list(headers, "access-control-allow-headers"),
+            expose_headers: header_list(headers, "access-control-expose-headers"),
+            allow_credentials: header_str("access-control-allow-credentials") == Some("true"),
+            max_age: header_str("access-control-max-age").and_then(|value| value.parse().ok()),
+        }
+    }
+
+    // Whether the preflight allows `method`, matching case-sensitively as browsers do
+    pub fn allows_method(&self, method: &str) -> bool {
+        self.allow_methods.iter().any(|allowed| allowed == "*" || allowed == method)
+    }
+
+    // Whether the preflight allows the request header `name`, matching case-insensitively
+    pub fn allows_header(&self, name: &str) -> bool {
+        self.allow_headers.iter().any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(name))
+    }
+}
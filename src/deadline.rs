@@ -0,0 +1,83 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use futures_lite::future;
+use simple_error::SimpleResult;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::HttpClientError;
+
+// One overall time budget for an operation made of several requests, e.g. authenticating and then fetching. Each
+// request sent with it (as a request extension, or through `run`) gets whatever time is left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deadline(pub Instant);
+
+impl Deadline {
+    pub fn after(budget: Duration) -> Self {
+        Self(Instant::now() + budget)
+    }
+
+    // A deadline `budget` from now on `clock`
+    pub fn after_on(clock: &dyn Clock, budget: Duration) -> Self {
+        Self(clock.now() + budget)
+    }
+
+    // Time left before the deadline, or DeadlineExceeded once it has passed
+    pub fn remaining(&self) -> SimpleResult<Duration> {
+        self.remaining_on(&SystemClock)
+    }
+
+    // Like `remaining`, reading the time from `clock`
+    pub fn remaining_on(&self, clock: &dyn Clock) -> SimpleResult<Duration> {
+        match self.0.checked_duration_since(clock.now()) {
+            Some(remaining) if !remaining.is_zero() => Ok(remaining),
+            _ => Err(HttpClientError::DeadlineExceeded.into()),
+        }
+    }
+
+    // The shorter of the time left and a limit of its own, for steps that have one
+    pub fn remaining_within(&self, limit: Duration) -> SimpleResult<Duration> {
+        self.remaining_within_on(&SystemClock, limit)
+    }
+
+    // Like `remaining_within`, reading the time from `clock`, e.g. RequestOptions::clock
+    pub fn remaining_within_on(&self, clock: &dyn Clock, limit: Duration) -> SimpleResult<Duration> {
+        Ok(self.remaining_on(clock)?.min(limit))
+    }
+
+    // Runs `future`, failing with DeadlineExceeded if it hasn't finished by the deadline
+    pub async fn run<T>(&self, future: impl Future<Output = SimpleResult<T>>) -> SimpleResult<T> {
+        self.run_on(&SystemClock, future).await
+    }
+
+    // Like `run`, timing the deadline on `clock`
+    pub async fn run_on<T>(&self, clock: &dyn Clock, future: impl Future<Output = SimpleResult<T>>) -> SimpleResult<T> {
+        let remaining = self.remaining_on(clock)?;
+        let expired = async {
+            clock.sleep(remaining).await;
+            Err(HttpClientError::DeadlineExceeded.into())
+        };
+        future::or(future, expired).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn remaining_within_follows_the_given_clock() {
+        let clock = MockClock::new();
+        let deadline = Deadline::after_on(&clock, Duration::from_secs(10));
+        assert_eq!(deadline.remaining_within_on(&clock, Duration::from_secs(30)).unwrap(), Duration::from_secs(10));
+
+        clock.advance(Duration::from_secs(8));
+        assert_eq!(deadline.remaining_within_on(&clock, Duration::from_secs(30)).unwrap(), Duration::from_secs(2));
+        assert_eq!(deadline.remaining_within_on(&clock, Duration::from_secs(1)).unwrap(), Duration::from_secs(1));
+
+        clock.advance(Duration::from_secs(2));
+        let err = deadline.remaining_within_on(&clock, Duration::from_secs(1)).unwrap_err();
+        assert!(matches!(err.downcast_ref::<HttpClientError>(), Some(HttpClientError::DeadlineExceeded)));
+    }
+}
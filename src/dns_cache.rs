@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use simple_error::SimpleResult;
+
+use crate::clock::{Clock, SystemClock};
+
+// How long a failed lookup is remembered unless configured otherwise
+const DEFAULT_NEGATIVE_TTL: Duration = Duration::from_secs(5);
+
+struct DnsCacheEntry {
+    // None records a failed lookup
+    addrs: Option<Vec<SocketAddr>>,
+    expires_at: Instant,
+}
+
+// In-memory cache of resolved addresses keyed by host:port, shared by every connection a client makes
+pub struct DnsCache {
+    ttl: Duration,
+    negative_ttl: Duration,
+    entries: Mutex<HashMap<String, DnsCacheEntry>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl DnsCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            negative_ttl: DEFAULT_NEGATIVE_TTL.min(ttl),
+            entries: Mutex::new(HashMap::new()),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    // Expires entries by `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    // Sets how long failed lookups are cached before the resolver is asked again
+    pub fn with_negative_ttl(mut self, negative_ttl: Duration) -> Self {
+        self.negative_ttl = negative_ttl;
+        self
+    }
+
+    // Returns the cached addresses for host:port, resolving and caching them when missing or expired
+    pub fn resolve(&self, host: &str, port: u16) -> SimpleResult<Vec<SocketAddr>> {
+        let key = format!("{host}:{port}");
+        let now = self.clock.now();
+
+        {
+            let entries = self.entries.lock().map_err(|_| "DNS cache lock poisoned")?;
+            if let Some(entry) = entries.get(&key).filter(|entry| entry.expires_at > now) {
+                log::debug!("dns cache hit for {key}");
+                return entry.addrs.clone().ok_or_else(|| format!("Failed to resolve host {host} (cached)").into());
+            }
+        }
+
+        // Resolve outside the lock so a slow lookup doesn't stall other hosts
+        let resolved = key.to_socket_addrs().map(|addrs| addrs.collect::<Vec<_>>());
+        let addrs = match &resolved {
+            Ok(addrs) if !addrs.is_empty() => Some(addrs.clone()),
+            _ => None,
+        };
+        let ttl = if addrs.is_some() { self.ttl } else { self.negative_ttl };
+
+        let mut entries = self.entries.lock().map_err(|_| "DNS cache lock poisoned")?;
+        entries.retain(|_, entry| entry.expires_at > now);
+        entries.insert(key, DnsCacheEntry { addrs: addrs.clone(), expires_at: now + ttl });
+
+        match (addrs, resolved) {
+            (Some(addrs), _) => Ok(addrs),
+            (None, Err(err)) => Err(err.into()),
+            (None, Ok(_)) => Err(format!("Failed to resolve host {host}").into()),
+        }
+    }
+
+    // Forgets every cached lookup
+    pub fn clear(&self) -> SimpleResult<()> {
+        self.entries.lock().map_err(|_| "DNS cache lock poisoned")?.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+use http::{header, HeaderMap, HeaderValue, Response, StatusCode};
+use simple_error::{box_err, SimpleResult};
+
+// Whether a server accepts byte range requests for a resource, from its Accept-Ranges header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AcceptRanges {
+    Bytes,
+    // The server said `none`, so resuming is pointless
+    None,
+    // The header is absent or names only other units. Servers may still honor Range, but can't be relied on to.
+    Unknown,
+}
+
+impl AcceptRanges {
+    pub fn from_headers(headers: &HeaderMap<HeaderValue>) -> Self {
+        let Some(accept_ranges) = headers.get(header::ACCEPT_RANGES).and_then(|value| value.to_str().ok()) else {
+            return AcceptRanges::Unknown;
+        };
+        let mut units = accept_ranges.split(',').map(str::trim);
+        if units.clone().any(|unit| unit.eq_ignore_ascii_case("bytes")) {
+            AcceptRanges::Bytes
+        } else if units.any(|unit| unit.eq_ignore_ascii_case("none")) {
+            AcceptRanges::None
+        } else {
+            AcceptRanges::Unknown
+        }
+    }
+
+    pub fn is_resumable(&self) -> bool {
+        *self == AcceptRanges::Bytes
+    }
+}
+
+// What a HEAD request revealed about a resource before downloading it
+#[derive(Clone, Debug)]
+pub struct DownloadTarget {
+    pub content_length: Option<u64>,
+    pub accept_ranges: AcceptRanges,
+    // Only strong validators can be used with If-Range
+    pub etag: Option<HeaderValue>,
+}
+
+impl DownloadTarget {
+    pub fn from_headers(headers: &HeaderMap<HeaderValue>) -> Self {
+        let etag = headers.get(header::ETAG).filter(|etag| !etag.as_bytes().starts_with(b"W/")).cloned();
+        let content_length = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok());
+        Self {
+            content_length,
+            accept_ranges: AcceptRanges::from_headers(headers),
+            etag,
+        }
+    }
+}
+
+// Parses a `bytes first-last/complete` Content-Range value into (first, last, complete length). The complete
+// length is None when the server sent `*`.
+pub fn parse_content_range(content_range: &str) -> Option<(u64, u64, Option<u64>)> {
+    let range = content_range.trim().strip_prefix("bytes ")?;
+    let (range, complete_length) = range.split_once('/')?;
+    let (first, last) = range.split_once('-')?;
+    let (first, last): (u64, u64) = (first.trim().parse().ok()?, last.trim().parse().ok()?);
+    let complete_length = match complete_length.trim() {
+        "*" => None,
+        complete_length => Some(complete_length.parse().ok()?),
+    };
+    if last < first {
+        return None;
+    }
+    Some((first, last, complete_length))
+}
+
+// First byte position of a `Content-Range: bytes first-last/complete` header
+pub fn content_range_start(headers: &HeaderMap<HeaderValue>) -> Option<u64> {
+    let content_range = headers.get(header::CONTENT_RANGE)?.to_str().ok()?;
+    parse_content_range(content_range).map(|(first, _, _)| first)
+}
+
+// Builds a Range header asking for several byte ranges at once, e.g. `bytes=0-99,200-299`. Each range is
+// (first, last) with `last` inclusive, or None for everything from `first` on.
+pub fn byte_ranges_header(ranges: &[(u64, Option<u64>)]) -> SimpleResult<HeaderValue> {
+    if ranges.is_empty() {
+        return Err(box_err!("No byte ranges provided"));
+    }
+    let mut specs = Vec::with_capacity(ranges.len());
+    for (first, last) in ranges {
+        match last {
+            Some(last) if last < first => return Err(format!("Byte range {first}-{last} ends before it starts").into()),
+            Some(last) => specs.push(format!("{first}-{last}")),
+            None => specs.push(format!("{first}-")),
+        }
+    }
+    Ok(HeaderValue::from_str(&format!("bytes={}", specs.join(",")))?)
+}
+
+// One range of a 206 response, with the bytes it covers
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ByteRange {
+    pub first: u64,
+    pub last: u64,
+    pub complete_length: Option<u64>,
+    pub data: Vec<u8>,
+}
+
+// Splits a 206 response into its ranges. A multipart/byteranges response has one part per range; any other 206
+// carries a single range described by its Content-Range header.
+pub fn byte_ranges(response: &Response<Vec<u8>>) -> SimpleResult<Vec<ByteRange>> {
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(format!("Expected a 206 response, got {}", response.status()).into());
+    }
+    let content_type = response.headers().get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).unwrap_or("");
+    let (media_type, parameters) = content_type.split_once(';').unwrap_or((content_type, ""));
+    if !media_type.trim().eq_ignore_ascii_case("multipart/byteranges") {
+        let content_range = response.headers().get(header::CONTENT_RANGE).ok_or("206 response without Content-Range")?.to_str()?;
+        let (first, last, complete_length) = parse_content_range(content_range).ok_or_else(|| format!("Invalid Content-Range: {content_range:?}"))?;
+        return Ok(vec![ByteRange {
+            first,
+            last,
+            complete_length,
+            data: response.body().clone(),
+        }]);
+    }
+
+    let boundary = parameters
+        .split(';')
+        .filter_map(|parameter| parameter.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"'))
+        .filter(|boundary| !boundary.is_empty())
+        .ok_or("multipart/byteranges response without a boundary")?;
+    split_byteranges(response.body(), boundary)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+// Splits a multipart/byteranges body into its parts. Each part's length comes from its Content-Range, so range
+// data that happens to contain the boundary can't cut a part short.
+fn split_byteranges(body: &[u8], boundary: &str) -> SimpleResult<Vec<ByteRange>> {
+    let delimiter = format!("--{boundary}");
+    let delimiter = delimiter.as_bytes();
+    let mut ranges = Vec::new();
+    // Anything before the first delimiter is preamble
+    let mut pos = find(body, delimiter).ok_or("multipart/byteranges body has no boundary")?;
+
+    loop {
+        pos += delimiter.len();
+        if body[pos..].starts_with(b"--") {
+            return Ok(ranges);
+        }
+        // Skip transport padding up to the end of the delimiter line
+        pos += find(&body[pos..], b"\n").ok_or("Truncated multipart/byteranges body")? + 1;
+
+        // Part headers, up to a blank line
+        let mut content_range = None;
+        loop {
+            let line_length = find(&body[pos..], b"\n").ok_or("Truncated multipart/byteranges part headers")?;
+            let line = &body[pos..pos + line_length];
+            pos += line_length + 1;
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.is_empty() {
+                break;
+            }
+            let line = std::str::from_utf8(line)?;
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-range") {
+                    content_range = Some(parse_content_range(value).ok_or_else(|| format!("Invalid Content-Range in part: {value:?}"))?);
+                }
+            }
+        }
+        let (first, last, complete_length) = content_range.ok_or("multipart/byteranges part without Content-Range")?;
+
+        let length = last.checked_sub(first).and_then(|span| span.checked_add(1)).ok_or_else(|| format!("Invalid byte range in part: {first}-{last}"))?;
+        let length = usize::try_from(length)?;
+        let data = body.get(pos..pos.saturating_add(length)).ok_or("Truncated multipart/byteranges part")?;
+        ranges.push(ByteRange {
+            first,
+            last,
+            complete_length,
+            data: data.to_vec(),
+        });
+        pos += length;
+
+        // The part ends with CRLF followed by the next delimiter
+        let next = find(&body[pos..], delimiter).ok_or("multipart/byteranges body is missing its final boundary")?;
+        pos += next;
+    }
+}
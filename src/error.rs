@@ -0,0 +1,207 @@
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+
+use http::Method;
+use simple_error::SimpleResult;
+
+use crate::connect_options::AddressFamily;
+
+// Why a TCP connection could not be established, so callers can fail fast on a refusal but retry a timeout
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectErrorReason {
+    Refused,
+    TimedOut,
+    HostUnreachable,
+    NetworkUnreachable,
+    Other,
+}
+
+impl From<io::ErrorKind> for ConnectErrorReason {
+    fn from(kind: io::ErrorKind) -> Self {
+        match kind {
+            io::ErrorKind::ConnectionRefused => ConnectErrorReason::Refused,
+            io::ErrorKind::TimedOut => ConnectErrorReason::TimedOut,
+            io::ErrorKind::HostUnreachable => ConnectErrorReason::HostUnreachable,
+            io::ErrorKind::NetworkUnreachable => ConnectErrorReason::NetworkUnreachable,
+            _ => ConnectErrorReason::Other,
+        }
+    }
+}
+
+impl fmt::Display for ConnectErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectErrorReason::Refused => write!(f, "connection refused"),
+            ConnectErrorReason::TimedOut => write!(f, "connection timed out"),
+            ConnectErrorReason::HostUnreachable => write!(f, "host unreachable"),
+            ConnectErrorReason::NetworkUnreachable => write!(f, "network unreachable"),
+            ConnectErrorReason::Other => write!(f, "connection failed"),
+        }
+    }
+}
+
+// How far a request got before it failed. Nothing reaches the server before the Write stage, so a request that
+// failed while connecting or while its head was being prepared can be retried whatever its method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestStage {
+    Connect,
+    // Serializing the head, computing the body checksum and running the before_send hook
+    Prepare,
+    Write,
+    Read,
+}
+
+impl RequestStage {
+    // Whether resending the request can't repeat a side effect. Once writing began the server may have acted
+    // on it, so only idempotent methods are safe.
+    pub fn is_retry_safe(&self, method: &Method) -> bool {
+        match self {
+            RequestStage::Connect | RequestStage::Prepare => true,
+            RequestStage::Write | RequestStage::Read => {
+                matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE)
+            }
+        }
+    }
+}
+
+impl fmt::Display for RequestStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestStage::Connect => write!(f, "connecting"),
+            RequestStage::Prepare => write!(f, "preparing the request"),
+            RequestStage::Write => write!(f, "writing the request"),
+            RequestStage::Read => write!(f, "reading the response"),
+        }
+    }
+}
+
+// Failures callers may want to tell apart. They travel boxed inside SimpleResult and can be recovered with downcast_ref.
+#[derive(Debug)]
+pub enum HttpClientError {
+    // The connection closed before the blank line ending the header section
+    UnexpectedEofInHeaders,
+    // Content-Length was not a non-negative integer that fits, or carried conflicting values
+    InvalidContentLength(String),
+    // The request was abandoned because its client was shut down
+    Cancelled,
+    // The host resolved, but to no address of the requested family
+    NoUsableAddress { host: String, family: AddressFamily },
+    // The connection already read as many bytes as its lifetime cap allows
+    ConnectionByteLimitExceeded(u64),
+    // The TLS handshake failed because the server can't speak any protocol version the client allows
+    TlsVersionNotSupported(String),
+    // The TCP connection to `addr` failed; `source` is the underlying io::Error
+    Connect { addr: SocketAddr, reason: ConnectErrorReason, source: io::Error },
+    // The circuit breaker for this origin is open after repeated failures
+    CircuitOpen(String),
+    // The response body is longer than this platform can hold in memory; stream it instead
+    ResponseTooLargeForPlatform(u64),
+    // Every connection slot for this origin is taken and the client is set to fail rather than wait
+    ConnectionLimitReached(String),
+    // The Deadline shared by the operation passed before this request finished
+    DeadlineExceeded,
+    // The status line ran past the configured maximum length without ending
+    StatusLineTooLong(usize),
+    // The response body is chunked but the client is set to refuse chunked framing
+    ChunkedNotAllowed,
+    // The response body is framed by connection close, but the client requires a length or chunked framing or the
+    // request asked to keep the connection alive
+    UnframedBodyNotAllowed,
+    // The response header section ran past the configured maximum size
+    HeadersTooLarge(usize),
+    // A response repeated this header name more often than the client allows
+    TooManyHeaderValues(String),
+    // A response body arrived slower than the configured minimum, in bytes per second
+    SlowDownload(u64),
+    // An untyped failure tagged with the stage the request had reached
+    RequestFailed { stage: RequestStage, source: Box<dyn std::error::Error + Send + Sync> },
+}
+
+impl fmt::Display for HttpClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpClientError::UnexpectedEofInHeaders => write!(f, "Connection closed before the end of the response headers"),
+            HttpClientError::InvalidContentLength(value) => write!(f, "Invalid Content-Length: {value}"),
+            HttpClientError::Cancelled => write!(f, "Request cancelled"),
+            HttpClientError::NoUsableAddress { host, family } => write!(f, "{host} has no {family} address"),
+            HttpClientError::ConnectionByteLimitExceeded(limit) => write!(f, "Connection exceeded its limit of {limit} bytes read"),
+            HttpClientError::TlsVersionNotSupported(host) => write!(f, "{host} does not support any allowed TLS version"),
+            HttpClientError::Connect { addr, reason, source } => write!(f, "Failed to connect to {addr}: {reason} ({source})"),
+            HttpClientError::CircuitOpen(origin) => write!(f, "Circuit open for {origin}, not sending request"),
+            HttpClientError::ResponseTooLargeForPlatform(length) => write!(f, "Response body of {length} bytes is too large to hold in memory"),
+            HttpClientError::ConnectionLimitReached(origin) => write!(f, "Connection limit reached for {origin}"),
+            HttpClientError::DeadlineExceeded => write!(f, "Deadline exceeded"),
+            HttpClientError::StatusLineTooLong(max_length) => write!(f, "Status line exceeds maximum length of {max_length} bytes"),
+            HttpClientError::ChunkedNotAllowed => write!(f, "Chunked response bodies are not allowed"),
+            HttpClientError::UnframedBodyNotAllowed => write!(f, "Response bodies framed by connection close are not allowed"),
+            HttpClientError::HeadersTooLarge(max_size) => write!(f, "Response headers exceed maximum size of {max_size} bytes"),
+            HttpClientError::TooManyHeaderValues(name) => write!(f, "Too many {name} header values"),
+            HttpClientError::SlowDownload(bytes_per_second) => write!(f, "Response body arrived slower than {bytes_per_second} bytes/s"),
+            HttpClientError::RequestFailed { stage, source } => write!(f, "Request failed while {stage}: {source}"),
+        }
+    }
+}
+
+impl HttpClientError {
+    // The stage the request had reached, when this error tells. Errors raised before a connection exists mean
+    // nothing was sent; errors about the response mean the request was fully written.
+    pub fn stage(&self) -> Option<RequestStage> {
+        match self {
+            HttpClientError::RequestFailed { stage, .. } => Some(*stage),
+            HttpClientError::NoUsableAddress { .. }
+            | HttpClientError::TlsVersionNotSupported(_)
+            | HttpClientError::Connect { .. }
+            | HttpClientError::CircuitOpen(_)
+            | HttpClientError::ConnectionLimitReached(_) => Some(RequestStage::Connect),
+            HttpClientError::UnexpectedEofInHeaders
+            | HttpClientError::InvalidContentLength(_)
+            | HttpClientError::ConnectionByteLimitExceeded(_)
+            | HttpClientError::ResponseTooLargeForPlatform(_)
+            | HttpClientError::StatusLineTooLong(_)
+            | HttpClientError::ChunkedNotAllowed
+            | HttpClientError::UnframedBodyNotAllowed
+            | HttpClientError::HeadersTooLarge(_)
+            | HttpClientError::TooManyHeaderValues(_)
+            | HttpClientError::SlowDownload(_) => Some(RequestStage::Read),
+            // These can strike at any point
+            HttpClientError::Cancelled | HttpClientError::DeadlineExceeded => None,
+        }
+    }
+}
+
+// Tags an untyped failure with the stage it happened in, keeping the original error as its source so an io::Error
+// and its kind can still be reached. Typed errors pass through untouched so they can still be downcast; their
+// stage comes from HttpClientError::stage.
+pub(crate) fn with_stage<T>(stage: RequestStage, result: SimpleResult<T>) -> SimpleResult<T> {
+    result.map_err(|err| {
+        let err = unwrap_io_error(err);
+        if err.downcast_ref::<HttpClientError>().is_some() {
+            return err;
+        }
+        HttpClientError::RequestFailed { stage, source: err }.into()
+    })
+}
+
+// AsyncRead and AsyncWrite can only fail with an io::Error, so ByteLimitedConnection wraps its typed error in one.
+// This takes it back out so callers can downcast it.
+pub(crate) fn unwrap_io_error(err: Box<dyn std::error::Error + Send + Sync>) -> Box<dyn std::error::Error + Send + Sync> {
+    let wraps_typed = err.downcast_ref::<io::Error>().and_then(io::Error::get_ref).is_some_and(|inner| inner.is::<HttpClientError>());
+    if !wraps_typed {
+        return err;
+    }
+    match err.downcast::<io::Error>() {
+        Ok(io_err) => io_err.into_inner().expect("checked for an inner error above"),
+        Err(err) => err,
+    }
+}
+
+impl std::error::Error for HttpClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HttpClientError::Connect { source, .. } => Some(source),
+            HttpClientError::RequestFailed { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
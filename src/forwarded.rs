@@ -0,0 +1,57 @@
+use std::net::IpAddr;
+
+use http::{header, HeaderMap, HeaderName, HeaderValue};
+use simple_error::SimpleResult;
+
+use crate::request::is_tchar;
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+// Forwarded parameter values are tokens, or quoted strings when they contain anything else (RFC 7239 section 4)
+fn forwarded_parameter_value(value: &str) -> String {
+    if !value.is_empty() && value.bytes().all(is_tchar) {
+        return value.to_string();
+    }
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{escaped}\"")
+}
+
+// Joins every line of a list-valued header, so the chain reads the same however it was split across lines
+fn joined_list(headers: &HeaderMap<HeaderValue>, name: &HeaderName) -> SimpleResult<Vec<String>> {
+    let mut elements = Vec::new();
+    for value in headers.get_all(name) {
+        let value = value.to_str()?.trim();
+        if !value.is_empty() {
+            elements.push(value.to_string());
+        }
+    }
+    Ok(elements)
+}
+
+// Forwarded value (RFC 7239) for a proxied request: the incoming chain with an element for `client` appended.
+// `proto` and `host` record the scheme and Host the client used, when the proxy knows them.
+pub fn forwarded_header(incoming: &HeaderMap<HeaderValue>, client: IpAddr, proto: Option<&str>, host: Option<&str>) -> SimpleResult<HeaderValue> {
+    // IPv6 addresses are bracketed and, containing colons, quoted
+    let client = match client {
+        IpAddr::V4(client) => client.to_string(),
+        IpAddr::V6(client) => format!("\"[{client}]\""),
+    };
+    let mut element = format!("for={client}");
+    if let Some(proto) = proto {
+        element.push_str(&format!(";proto={}", forwarded_parameter_value(proto)));
+    }
+    if let Some(host) = host {
+        element.push_str(&format!(";host={}", forwarded_parameter_value(host)));
+    }
+
+    let mut chain = joined_list(incoming, &header::FORWARDED)?;
+    chain.push(element);
+    Ok(HeaderValue::from_str(&chain.join(", "))?)
+}
+
+// X-Forwarded-For value for a proxied request: the incoming list of addresses with `client` appended
+pub fn x_forwarded_for_header(incoming: &HeaderMap<HeaderValue>, client: IpAddr) -> SimpleResult<HeaderValue> {
+    let mut chain = joined_list(incoming, &X_FORWARDED_FOR)?;
+    chain.push(client.to_string());
+    Ok(HeaderValue::from_str(&chain.join(", "))?)
+}
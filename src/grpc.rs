@@ -0,0 +1,90 @@
+use http::{HeaderMap, HeaderName, HeaderValue, Response};
+use simple_error::SimpleResult;
+
+use crate::response::Trailers;
+
+// Flag marking a gRPC-Web frame that carries trailers instead of a message
+const GRPC_WEB_TRAILER_FLAG: u8 = 0x80;
+
+// Outcome of a gRPC call, from the grpc-status and grpc-message fields
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrpcStatus {
+    pub code: u32,
+    pub message: Option<String>,
+}
+
+impl GrpcStatus {
+    pub fn is_ok(&self) -> bool {
+        self.code == 0
+    }
+
+    fn from_fields(fields: &HeaderMap<HeaderValue>) -> SimpleResult<Option<Self>> {
+        let Some(code) = fields.get("grpc-status") else {
+            return Ok(None);
+        };
+        let code = code.to_str()?.trim().parse().map_err(|_| format!("Invalid grpc-status: {code:?}"))?;
+        let message = match fields.get("grpc-message") {
+            Some(message) => Some(percent_decode(message.to_str()?)?),
+            None => None,
+        };
+        Ok(Some(Self { code, message }))
+    }
+}
+
+// grpc-message is percent-encoded UTF-8
+fn percent_decode(value: &str) -> SimpleResult<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() && bytes[i + 1].is_ascii_hexdigit() && bytes[i + 2].is_ascii_hexdigit() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3])?;
+            decoded.push(u8::from_str_radix(hex, 16)?);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    Ok(String::from_utf8(decoded)?)
+}
+
+// The gRPC status of a response. It is looked up in the trailers first, then in the headers, where a
+// trailers-only response (an error with no messages) puts it. Trailers are kept apart from the headers in the
+// Trailers extension, so a field sent in both places is never clobbered.
+pub fn grpc_status<T>(response: &Response<T>) -> SimpleResult<Option<GrpcStatus>> {
+    if let Some(Trailers(trailers)) = response.extensions().get::<Trailers>() {
+        if let Some(status) = GrpcStatus::from_fields(trailers)? {
+            return Ok(Some(status));
+        }
+    }
+    GrpcStatus::from_fields(response.headers())
+}
+
+// gRPC-Web sends trailers inside the body as a final length-prefixed frame. Returns the fields of that frame, or
+// an empty map when the body has none.
+pub fn grpc_web_body_trailers(body: &[u8]) -> SimpleResult<HeaderMap<HeaderValue>> {
+    let mut trailers = HeaderMap::new();
+    let mut rest = body;
+
+    while !rest.is_empty() {
+        if rest.len() < 5 {
+            return Err("Truncated gRPC-Web frame header".into());
+        }
+        let flags = rest[0];
+        let length = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+        let end = length.checked_add(5).ok_or("gRPC-Web frame length overflows")?;
+        let payload = rest.get(5..end).ok_or("Truncated gRPC-Web frame")?;
+        rest = &rest[end..];
+
+        if flags & GRPC_WEB_TRAILER_FLAG == 0 {
+            continue;
+        }
+        for line in std::str::from_utf8(payload)?.split("\r\n").filter(|line| !line.is_empty()) {
+            let (name, value) = line.split_once(':').ok_or_else(|| format!("Invalid gRPC-Web trailer: {line:?}"))?;
+            trailers.append(HeaderName::from_bytes(name.trim().to_ascii_lowercase().as_bytes())?, HeaderValue::from_str(value.trim())?);
+        }
+    }
+
+    Ok(trailers)
+}
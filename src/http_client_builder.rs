@@ -0,0 +1,246 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use http::{HeaderValue, Request};
+use rustls::ClientConfig;
+use simple_error::SimpleResult;
+
+use crate::auth::AuthProvider;
+use crate::cancellation_token::CancellationToken;
+use crate::checksum::BodyChecksum;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::clock::Clock;
+use crate::connect_options::{AddressFamily, ConnectOptions};
+use crate::connection_limiter::ConnectionLimiter;
+use crate::dns_cache::DnsCache;
+use crate::request_options::{BeforeSend, MinDownloadSpeed, RequestOptions, TrailingDataPolicy};
+use crate::tls_config::TlsConfig;
+use crate::transport::{SocketTransport, Transport};
+use crate::HttpClient;
+
+// Builds an HttpClient whose configuration is shared by every request made through it
+#[derive(Default)]
+pub struct HttpClientBuilder {
+    connect_options: ConnectOptions,
+    request_options: RequestOptions,
+    default_accept: Option<HeaderValue>,
+    tls_config: Option<TlsConfig>,
+    tls_client_config: Option<Arc<ClientConfig>>,
+    transport: Option<Arc<dyn Transport>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    connection_limiter: Option<Arc<ConnectionLimiter>>,
+    auth_providers: Vec<Arc<dyn AuthProvider>>,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl HttpClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Caches DNS lookups for `ttl`, shared across all connections made by the client
+    pub fn dns_cache(mut self, ttl: Duration) -> Self {
+        self.connect_options.dns_cache = Some(Arc::new(DnsCache::new(ttl)));
+        self
+    }
+
+    // Shares an existing DNS cache, e.g. between several clients
+    pub fn shared_dns_cache(mut self, dns_cache: Arc<DnsCache>) -> Self {
+        self.connect_options.dns_cache = Some(dns_cache);
+        self
+    }
+
+    // Restricts connections to IPv4 or IPv6 addresses
+    pub fn address_family(mut self, address_family: AddressFamily) -> Self {
+        self.connect_options.address_family = address_family;
+        self
+    }
+
+    // Caps the total bytes read on each connection, as a backstop against a server streaming without end
+    pub fn max_connection_read_bytes(mut self, limit: u64) -> Self {
+        self.connect_options.max_connection_read_bytes = Some(limit);
+        self
+    }
+
+    // Accept header sent when a request doesn't specify its own
+    pub fn default_accept(mut self, accept: HeaderValue) -> Self {
+        self.default_accept = Some(accept);
+        self
+    }
+
+    // Longest chunk-size or trailer line accepted in chunked responses
+    pub fn max_chunk_line_length(mut self, max_chunk_line_length: usize) -> Self {
+        self.request_options.max_chunk_line_length = max_chunk_line_length;
+        self
+    }
+
+    // Sends each request head and body in a single write
+    pub fn coalesce_writes(mut self, coalesce_writes: bool) -> Self {
+        self.request_options.coalesce_writes = coalesce_writes;
+        self
+    }
+
+    // Longest response status line accepted before failing with StatusLineTooLong
+    pub fn max_status_line_length(mut self, max_status_line_length: usize) -> Self {
+        self.request_options.max_status_line_length = max_status_line_length;
+        self
+    }
+
+    // Whether chunked response bodies are accepted; on by default
+    pub fn allow_chunked(mut self, allow_chunked: bool) -> Self {
+        self.request_options.allow_chunked = allow_chunked;
+        self
+    }
+
+    // Rejects response bodies that are framed only by the connection closing
+    pub fn require_framed_body(mut self, require_framed_body: bool) -> Self {
+        self.request_options.require_framed_body = require_framed_body;
+        self
+    }
+
+    // Caps the size of a response's header section
+    pub fn max_header_section_size(mut self, max_header_section_size: usize) -> Self {
+        self.request_options.max_header_section_size = max_header_section_size;
+        self
+    }
+
+    // Caps how many values one header name may collect in a response. With `reject_excess`, going over fails the
+    // response instead of dropping the extras.
+    pub fn max_header_values_per_name(mut self, max_values_per_name: usize, reject_excess: bool) -> Self {
+        self.request_options.max_header_values_per_name = max_values_per_name;
+        self.request_options.reject_excess_header_values = reject_excess;
+        self
+    }
+
+    // Fails response bodies that arrive slower than `bytes_per_second`, after a few seconds' grace
+    pub fn min_download_speed(mut self, bytes_per_second: u64) -> Self {
+        self.request_options.min_download_speed = Some(MinDownloadSpeed::new(bytes_per_second));
+        self
+    }
+
+    // Attaches the request head as written to each response in a SentRequest extension
+    pub fn retain_sent_request(mut self, retain_sent_request: bool) -> Self {
+        self.request_options.retain_sent_request = retain_sent_request;
+        self
+    }
+
+    // How long to wait for 100 Continue before sending the body of an `Expect: 100-continue` request. Zero sends it
+    // immediately.
+    pub fn expect_continue_timeout(mut self, expect_continue_timeout: Duration) -> Self {
+        self.request_options.expect_continue_timeout = expect_continue_timeout;
+        self
+    }
+
+    // Gives up on an unframed response body once the server goes quiet for `idle_timeout`
+    pub fn unframed_body_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.request_options.unframed_body_idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    // Adds a Date header to requests that don't set their own
+    pub fn send_date(mut self, send_date: bool) -> Self {
+        self.request_options.send_date = send_date;
+        self
+    }
+
+    // Runs `hook` on every request head right before it is written, after Host, Content-Length and Date are in place
+    pub fn before_send<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut Request<()>) -> SimpleResult<()> + Send + Sync + 'static,
+    {
+        self.request_options.before_send = Some(BeforeSend(Arc::new(hook)));
+        self
+    }
+
+    // TLS settings, loaded into a rustls config when the client is built
+    pub fn tls_config(mut self, tls_config: TlsConfig) -> Self {
+        self.tls_config = Some(tls_config);
+        self
+    }
+
+    // Uses the given rustls config verbatim for TLS connections. It takes precedence over `tls_config`,
+    // whose settings are ignored when both are given. ALPN protocols other than http/1.1 are dropped from the offer,
+    // and a config that doesn't offer http/1.1 at all fails to connect.
+    pub fn tls_client_config(mut self, tls_client_config: Arc<ClientConfig>) -> Self {
+        self.tls_client_config = Some(tls_client_config);
+        self
+    }
+
+    // Dispatches requests through a custom transport, such as a MockTransport in tests.
+    // Connection and TLS settings only apply to the default socket transport.
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    // Short-circuits requests to origins that keep failing. The breaker can be shared between clients.
+    pub fn circuit_breaker(mut self, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    // Times request deadlines, min_download_speed, unframed_body_idle_timeout and expect_continue_timeout on
+    // `clock`, e.g. a MockClock in tests. A CircuitBreaker or DnsCache takes its own clock via with_clock.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.request_options.clock = clock.clone();
+        self.clock = Some(clock);
+        self
+    }
+
+    // Attaches a checksum of each request body, e.g. BodyChecksum::content_md5()
+    pub fn body_checksum(mut self, body_checksum: BodyChecksum) -> Self {
+        self.request_options.body_checksum = Some(body_checksum);
+        self
+    }
+
+    // What to do with bytes sent after the end of a complete response
+    pub fn trailing_data_policy(mut self, trailing_data: TrailingDataPolicy) -> Self {
+        self.request_options.trailing_data = trailing_data;
+        self
+    }
+
+    // Opens at most `max_per_origin` connections to each origin at once across all requests made through the
+    // client. Further requests wait for a slot, or fail with ConnectionLimitReached when `fail_fast` is set.
+    pub fn max_connections_per_origin(mut self, max_per_origin: usize, fail_fast: bool) -> Self {
+        self.connection_limiter = Some(Arc::new(ConnectionLimiter::new(max_per_origin, fail_fast)));
+        self
+    }
+
+    // Adds a provider for answering 401 challenges. Providers are asked in the order they were added.
+    pub fn auth_provider(mut self, auth_provider: Arc<dyn AuthProvider>) -> Self {
+        self.auth_providers.push(auth_provider);
+        self
+    }
+
+    pub fn build(mut self) -> SimpleResult<HttpClient> {
+        self.connect_options.tls_client_config = match (self.tls_client_config, &self.tls_config) {
+            (Some(tls_client_config), tls_config) => {
+                if tls_config.is_some() {
+                    log::warn!("Both tls_client_config and tls_config were set, ignoring tls_config");
+                }
+                Some(tls_client_config)
+            }
+            (None, Some(tls_config)) => Some(tls_config.build_client_config()?),
+            (None, None) => None,
+        };
+
+        let clock = self.clock.unwrap_or_else(|| self.request_options.clock.clone());
+        let transport: Arc<dyn Transport> = match self.transport {
+            Some(transport) => transport,
+            None => Arc::new(SocketTransport {
+                connect_options: self.connect_options,
+                request_options: self.request_options,
+            }),
+        };
+
+        Ok(HttpClient {
+            transport,
+            default_accept: self.default_accept,
+            shutdown: CancellationToken::new(),
+            circuit_breaker: self.circuit_breaker,
+            connection_limiter: self.connection_limiter,
+            auth_providers: self.auth_providers,
+            clock,
+        })
+    }
+}
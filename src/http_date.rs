@@ -0,0 +1,99 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+// Converts days since 1970-01-01 to a (year, month, day) civil date (Howard Hinnant's days_from_civil, inverted)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// Converts a (year, month, day) civil date to days since 1970-01-01 (Howard Hinnant's days_from_civil)
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Parses a date the lenient way browsers parse cookie dates (RFC 6265 section 5.1.1), which accepts
+// IMF-fixdate, RFC 850 and asctime dates alike. Dates before the epoch come back as the epoch.
+pub fn parse_cookie_date(date: &str) -> Option<SystemTime> {
+    let mut time = None;
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+
+    let is_delimiter = |c: char| !(c.is_ascii_alphanumeric() || c == ':');
+    for token in date.split(is_delimiter).filter(|token| !token.is_empty()) {
+        let leading_digits = token.bytes().take_while(u8::is_ascii_digit).count();
+        if time.is_none() {
+            let parts: Vec<&str> = token.split(':').collect();
+            if parts.len() == 3 && parts.iter().all(|part| (1..=2).contains(&part.len()) && part.bytes().all(|b| b.is_ascii_digit())) {
+                let parts: Vec<u32> = parts.iter().map(|part| part.parse().unwrap_or(0)).collect();
+                time = Some((parts[0], parts[1], parts[2]));
+                continue;
+            }
+        }
+        if day.is_none() && (1..=2).contains(&leading_digits) {
+            day = token[..leading_digits].parse::<u32>().ok();
+            continue;
+        }
+        if month.is_none() && token.len() >= 3 {
+            if let Some(index) = MONTHS.iter().position(|name| name.eq_ignore_ascii_case(&token[..3])) {
+                month = Some(index as u32 + 1);
+                continue;
+            }
+        }
+        if year.is_none() && (2..=4).contains(&leading_digits) {
+            year = token[..leading_digits].parse::<i64>().ok();
+        }
+    }
+
+    let (hour, minute, second) = time?;
+    let (day, month) = (day?, month?);
+    // Two-digit years: 70-99 are 1970-1999, 00-69 are 2000-2069
+    let year = match year? {
+        year @ 70..=99 => year + 1900,
+        year @ 0..=69 => year + 2000,
+        year => year,
+    };
+    if !(1..=31).contains(&day) || year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let secs = days_from_civil(year, month, day) * 86_400 + i64::from(hour * 3600 + minute * 60 + second);
+    Some(UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64))
+}
+
+// Formats the time as an IMF-fixdate (RFC 9110), e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn format_http_date(time: SystemTime) -> String {
+    // Times before the epoch are clamped; servers have no use for them anyway
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs()) as i64;
+    let days = secs.div_euclid(86_400);
+    let secs_of_day = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[days.rem_euclid(7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
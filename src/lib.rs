@@ -1,61 +1,869 @@
 mod async_connection_factory;
 mod async_connection;
+mod auth;
+mod body_stream;
+mod cancellation_token;
+mod checksum;
+mod circuit_breaker;
+mod clock;
+mod connect_options;
+mod connection_limiter;
+mod cookie;
+mod cors;
+mod deadline;
+mod dns_cache;
+mod download;
+mod error;
+mod forwarded;
+mod grpc;
+mod http_client_builder;
+mod http_date;
+mod mock_transport;
+mod prepared_request;
+mod redirect;
 mod request;
+mod request_builder;
+mod request_options;
 mod response;
+mod sniff;
+mod spooled_body;
+mod sse;
+mod tee_reader;
+mod tls_config;
+mod transport;
+mod upgrade;
+
+use std::future::Future;
+use std::net::TcpStream;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Poll;
 
-use async_connection::AsyncConnection;
 use async_connection_factory::AsyncConnectionFactory;
-use futures_lite::{io::BufReader, AsyncWriteExt};
-use http::{Request, Response, StatusCode, Uri};
-use simple_error::SimpleResult;
+use download::DownloadTarget;
+use request_options::DEFAULT_MAX_CHUNK_LINE_LENGTH;
+use tee_reader::TeeReader;
+use futures_lite::{future, io, io::BufReader, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use http::{header, HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode, Uri, Version};
+use simple_error::{box_err, SimpleResult};
+
+pub use async_connection::{AsyncConnection, ByteLimitedConnection, GenericConnection};
+pub use auth::{challenges, parse_challenges, AuthProvider, BasicAuth, BearerAuth, Challenge, DigestAuth};
+pub use body_stream::BodyStream;
+pub use cancellation_token::CancellationToken;
+pub use checksum::{BodyChecksum, ChecksumAlgorithm, ChecksumEncoding};
+pub use circuit_breaker::{CircuitBreaker, CircuitState};
+pub use clock::{Clock, MockClock, SleepFuture, SystemClock};
+pub use connect_options::{AddressFamily, ConnectOptions, ConnectOverrides};
+pub use connection_limiter::ConnectionLimiter;
+pub use cookie::{cookies, parse_set_cookie, parse_set_cookie_pair, parse_set_cookies, Cookie, SameSite};
+pub use cors::CorsPolicy;
+pub use deadline::Deadline;
+pub use dns_cache::DnsCache;
+pub use download::{byte_ranges, byte_ranges_header, parse_content_range, AcceptRanges, ByteRange};
+pub use error::{ConnectErrorReason, HttpClientError, RequestStage};
+pub use forwarded::{forwarded_header, x_forwarded_for_header};
+pub use grpc::{grpc_status, grpc_web_body_trailers, GrpcStatus};
+pub use http_client_builder::HttpClientBuilder;
+pub use mock_transport::MockTransport;
+pub use prepared_request::PreparedRequest;
+pub use redirect::resolve_redirect;
+pub use request::{accept_list, accept_trailers, insert_header_preserving_case, HeaderCaseMap};
+pub use request_builder::RequestBuilder;
+pub use request_options::{BeforeSend, MinDownloadSpeed, RequestOptions, TrailingData, TrailingDataPolicy};
+pub use response::{connection_reusable, read_response_body_into, response_json, response_text, BodyFraming, SentRequest, Trailers, UploadAborted};
+pub use sniff::{effective_content_type, sniff_content_type};
+pub use spooled_body::{SpooledBody, TempFile};
+pub use sse::{Event, EventParser, EventStream};
+pub use tls_config::{TlsConfig, TlsVersion};
+pub use transport::{SocketTransport, Transport, TransportFuture};
+pub use upgrade::{request_h2c_upgrade, request_upgrade, Upgraded};
+
+// Re-exported so callers build ClientConfig against the same rustls version this crate links
+pub use rustls;
 
 type RequestBody = Vec<u8>;
 type ResponseBody = Vec<u8>;
+type ResponseFuture = Pin<Box<dyn Future<Output = SimpleResult<Response<ResponseBody>>>>>;
+type ConnectFuture<'a> = Pin<Box<dyn Future<Output = SimpleResult<Box<dyn AsyncConnection>>> + 'a>>;
+
+#[derive(Clone)]
+pub struct HttpClient {
+    transport: Arc<dyn Transport>,
+    default_accept: Option<HeaderValue>,
+    shutdown: CancellationToken,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    connection_limiter: Option<Arc<ConnectionLimiter>>,
+    auth_providers: Vec<Arc<dyn AuthProvider>>,
+    clock: Arc<dyn Clock>,
+}
 
-pub struct HttpClient;
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self {
+            transport: Arc::new(SocketTransport::default()),
+            default_accept: None,
+            shutdown: CancellationToken::new(),
+            circuit_breaker: None,
+            connection_limiter: None,
+            auth_providers: Vec::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
 
 impl HttpClient {
+    pub fn builder() -> HttpClientBuilder {
+        HttpClientBuilder::new()
+    }
+
     pub async fn create_connection<T: std::fmt::Debug>(request: &Request<T>) -> SimpleResult<Box<dyn AsyncConnection>> {
-        AsyncConnectionFactory::connect(&request).await
+        AsyncConnectionFactory::connect(request).await
     }
 
-    // Public method to send an HTTP request and return the HTTP response
-    pub async fn request(stream: &mut Box<dyn AsyncConnection>, request: &Request<RequestBody>) -> SimpleResult<Response<ResponseBody>> {
-        // Write the HTTP request to the stream
-        let serialized_request = request::serialize_http_request(request)?;
-        log::debug!("serialized_request = {serialized_request}");
-        stream.write_all(serialized_request.as_bytes()).await?;
+    // Opens a connection for the request with explicit connection options, e.g. a caller-provided rustls config
+    pub async fn create_connection_with_options<T: std::fmt::Debug>(request: &Request<T>, options: &ConnectOptions) -> SimpleResult<Box<dyn AsyncConnection>> {
+        AsyncConnectionFactory::connect_with_options(request, options).await
+    }
+
+    // Wraps a connected TcpStream opened elsewhere (an inherited or socket-activated fd) so requests can be sent on
+    // it. Use `connection_from_tcp_stream_with_tls` for https.
+    pub fn connection_from_tcp_stream(stream: TcpStream) -> SimpleResult<Box<dyn AsyncConnection>> {
+        Ok(Box::new(AsyncConnectionFactory::from_tcp_stream(stream)?))
+    }
+
+    pub async fn connection_from_tcp_stream_with_tls(stream: TcpStream, server_name: &str, options: &ConnectOptions) -> SimpleResult<Box<dyn AsyncConnection>> {
+        AsyncConnectionFactory::from_tcp_stream_with_tls(stream, server_name, options).await
+    }
+
+    /// Adopts an inherited socket file descriptor, e.g. fd 3 under systemd socket activation
+    ///
+    /// # Safety
+    /// `fd` must be an open socket that nothing else owns or will close
+    #[cfg(unix)]
+    pub unsafe fn connection_from_raw_fd(fd: std::os::fd::RawFd) -> SimpleResult<Box<dyn AsyncConnection>> {
+        use std::os::fd::FromRawFd;
+        Self::connection_from_tcp_stream(TcpStream::from_raw_fd(fd))
+    }
+
+    // Opens a connection for the request on async-std's TcpStream instead of async-io's
+    #[cfg(feature = "async-std")]
+    pub async fn create_connection_with_async_std<T: std::fmt::Debug>(request: &Request<T>, options: &ConnectOptions) -> SimpleResult<Box<dyn AsyncConnection>> {
+        AsyncConnectionFactory::connect_with_async_std(request, options).await
+    }
+
+    // Closes a connection cleanly once it is done with: TLS connections send close_notify, so the server can tell the
+    // close from a truncation, and the TCP write side is shut down. Failures are only logged, since the server may
+    // well have closed its end already.
+    pub async fn close(stream: &mut Box<dyn AsyncConnection>) {
+        if let Err(err) = stream.close().await {
+            log::debug!("failed to close connection cleanly: {err}");
+        }
+    }
+
+    // Fills in headers the client is configured to default; anything set on the request wins
+    fn apply_defaults(&self, request: &mut Request<RequestBody>) {
+        if let Some(accept) = &self.default_accept {
+            request.headers_mut().entry(header::ACCEPT).or_insert_with(|| accept.clone());
+        }
+    }
+
+    // Sends the request through this client's transport, which by default opens a connection for it
+    pub async fn send(&self, mut request: Request<RequestBody>) -> SimpleResult<Response<ResponseBody>> {
+        self.apply_defaults(&mut request);
+        request::close_connection_by_default(&mut request);
+        let response = self.send_once(&request).await?;
+
+        // Answer an authentication challenge once. A request that already carried credentials isn't retried, so a
+        // rejected answer comes back as the second 401 rather than looping.
+        if response.status() != StatusCode::UNAUTHORIZED || self.auth_providers.is_empty() || request.headers().contains_key(header::AUTHORIZATION) {
+            return Ok(response);
+        }
+        let Some(authorization) = auth::authorize(&self.auth_providers, &request, response.headers())? else {
+            log::debug!("no auth provider answers the challenges in {:?}", response.headers().get_all(header::WWW_AUTHENTICATE));
+            return Ok(response);
+        };
+        request.headers_mut().insert(header::AUTHORIZATION, authorization);
+        self.send_once(&request).await
+    }
+
+    // One attempt at the request, with shutdown, the connection limit, its Deadline and the circuit breaker applied
+    async fn send_once(&self, request: &Request<RequestBody>) -> SimpleResult<Response<ResponseBody>> {
+        // Race the request against shutdown, dropping its connection if the client is shut down first
+        if self.shutdown.is_cancelled() {
+            return Err(HttpClientError::Cancelled.into());
+        }
+        let cancelled = async {
+            self.shutdown.cancelled().await;
+            Err(HttpClientError::Cancelled.into())
+        };
+
+        // Each request opens its own connection, so a connection slot is held until its response has been read
+        let origin = CircuitBreaker::origin(request.uri());
+        let limited_roundtrip = async {
+            let _permit = match &self.connection_limiter {
+                Some(connection_limiter) => Some(connection_limiter.acquire(&origin).await?),
+                None => None,
+            };
+            self.transport.roundtrip(request).await
+        };
+        // A request carrying a Deadline gets only the time the operation has left, waiting for a slot included
+        let roundtrip = async {
+            match request.extensions().get::<Deadline>() {
+                Some(deadline) => deadline.run_on(&*self.clock, limited_roundtrip).await,
+                None => limited_roundtrip.await,
+            }
+        };
+
+        // Fail fast while the origin's circuit is open, and feed the outcome back to the breaker
+        let started = self.clock.now();
+        let result = match &self.circuit_breaker {
+            Some(circuit_breaker) => {
+                circuit_breaker.acquire(&origin)?;
+                let result = future::or(roundtrip, cancelled).await;
+                circuit_breaker.record(&origin, &result);
+                result
+            }
+            None => future::or(roundtrip, cancelled).await,
+        };
+
+        // The outcome also goes out as key-value fields, for log pipelines that index them
+        let (method, uri, duration_ms) = (request.method().as_str(), request.uri(), self.clock.now().saturating_duration_since(started).as_millis() as u64);
+        match &result {
+            Ok(response) => log::debug!(
+                method = method, uri:% = uri, status = response.status().as_u16(), bytes = response.body().len(), duration_ms = duration_ms;
+                "{method} {uri} -> {} in {duration_ms}ms", response.status()
+            ),
+            Err(err) => log::debug!(method = method, uri:% = uri, duration_ms = duration_ms; "{method} {uri} failed in {duration_ms}ms: {err}"),
+        }
+        result
+    }
+
+    // The circuit breaker this client consults, for monitoring its state
+    pub fn circuit_breaker(&self) -> Option<&Arc<CircuitBreaker>> {
+        self.circuit_breaker.as_ref()
+    }
+
+    // The per-origin connection limiter this client enforces, for monitoring
+    pub fn connection_limiter(&self) -> Option<&Arc<ConnectionLimiter>> {
+        self.connection_limiter.as_ref()
+    }
+
+    // Starts a request with `method` to `uri`, sent through this client by RequestBuilder::send
+    pub fn request_builder(&self, method: Method, uri: &str) -> RequestBuilder<'_> {
+        RequestBuilder::new(self, method, uri)
+    }
+
+    pub fn get(&self, uri: &str) -> RequestBuilder<'_> {
+        self.request_builder(Method::GET, uri)
+    }
+
+    pub fn head(&self, uri: &str) -> RequestBuilder<'_> {
+        self.request_builder(Method::HEAD, uri)
+    }
+
+    pub fn post(&self, uri: &str, body: impl Into<Vec<u8>>) -> RequestBuilder<'_> {
+        self.request_builder(Method::POST, uri).body(body)
+    }
+
+    pub fn put(&self, uri: &str, body: impl Into<Vec<u8>>) -> RequestBuilder<'_> {
+        self.request_builder(Method::PUT, uri).body(body)
+    }
+
+    pub fn patch(&self, uri: &str, body: impl Into<Vec<u8>>) -> RequestBuilder<'_> {
+        self.request_builder(Method::PATCH, uri).body(body)
+    }
+
+    pub fn delete(&self, uri: &str) -> RequestBuilder<'_> {
+        self.request_builder(Method::DELETE, uri)
+    }
+
+    // Cancels every in-flight request made through this client (and its clones) and fails later ones
+    // with a Cancelled error
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    // Serializes the request head as it will be written, with automatic headers, the body checksum and the
+    // before_send hook applied
+    fn serialize_request(request: &Request<RequestBody>, options: &RequestOptions) -> SimpleResult<String> {
+        let body_length = Some(request.body().len());
+        if options.before_send.is_none() && options.body_checksum.is_none() {
+            return request::serialize_http_request(request, body_length, options);
+        }
+        let head = Self::sent_head(request, options)?;
+        request::serialize_http_request(&head, body_length, options)
+    }
+
+    // The request head exactly as it will be written
+    fn sent_head(request: &Request<RequestBody>, options: &RequestOptions) -> SimpleResult<Request<()>> {
+        let mut head = request::with_auto_headers(request, Some(request.body().len()), options)?;
+        if let Some(body_checksum) = &options.body_checksum {
+            if !head.headers().contains_key(&body_checksum.header_name) {
+                head.headers_mut().insert(body_checksum.header_name.clone(), body_checksum.compute(request.body())?);
+            }
+        }
+        if let Some(before_send) = &options.before_send {
+            (before_send.0)(&mut head)?;
+        }
+        Ok(head)
+    }
+
+    // Writes the HTTP request head and body to the stream. Returns the head as written when
+    // options.retain_sent_request asks for it.
+    async fn write_request(stream: &mut Box<dyn AsyncConnection>, request: &Request<RequestBody>, options: &RequestOptions) -> SimpleResult<Option<Request<()>>> {
+        let (serialized_request, sent_head) = Self::serialize_for_write(request, options)?;
+        log::debug!(method = request.method().as_str(), uri:% = request.uri(), bytes = serialized_request.len() + request.body().len(); "serialized_request = {serialized_request}");
+        Self::write_head_and_body(stream, serialized_request.as_bytes(), request.body(), request.headers(), options).await?;
+        Ok(sent_head)
+    }
+
+    // Serializes the request head, along with the head itself when options.retain_sent_request asks for it
+    fn serialize_for_write(request: &Request<RequestBody>, options: &RequestOptions) -> SimpleResult<(String, Option<Request<()>>)> {
+        if !options.retain_sent_request {
+            return Ok((Self::serialize_request(request, options)?, None));
+        }
+        let head = Self::sent_head(request, options)?;
+        Ok((request::serialize_http_request(&head, Some(request.body().len()), options)?, Some(head)))
+    }
+
+    async fn write_head_and_body<W>(stream: &mut W, head: &[u8], body: &[u8], request_headers: &HeaderMap, options: &RequestOptions) -> SimpleResult<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        // Send head and body together when asked to, unless the body has to wait for a 100 Continue
+        if options.coalesce_writes && !request::expects_continue(request_headers) {
+            let mut buffer = Vec::with_capacity(head.len() + body.len());
+            buffer.extend_from_slice(head);
+            buffer.extend_from_slice(body);
+            stream.write_all(&buffer).await?;
+            stream.flush().await?;
+            return Ok(());
+        }
+
+        stream.write_all(head).await?;
         stream.flush().await?;
 
         // Write request body if there is one
-        if request.body().len() > 0 {
-            stream.write_all(request.body()).await?;
+        if !body.is_empty() {
+            stream.write_all(body).await?;
             stream.flush().await?;
         }
 
-        // Read and parse the response
+        Ok(())
+    }
+
+    // Serializes the request once for sending many times with `send_prepared`, e.g. from a polling loop
+    pub fn prepare(request: &Request<RequestBody>, options: &RequestOptions) -> SimpleResult<PreparedRequest> {
+        let head = Self::serialize_request(request, options)?;
+        Ok(PreparedRequest::new(head.into_bytes(), request, options))
+    }
+
+    // Sends a prepared request with `body`, which must be as long as the body it was prepared with since its
+    // Content-Length is part of the frozen head. With a body checksum or before_send hook it must be that very body.
+    pub async fn send_prepared(stream: &mut Box<dyn AsyncConnection>, prepared: &PreparedRequest, body: &[u8]) -> SimpleResult<Response<ResponseBody>> {
+        if body.len() != prepared.body_length {
+            return Err(format!("Prepared request expects a {} byte body, got {}", prepared.body_length, body.len()).into());
+        }
+        if prepared.bound_body.as_deref().is_some_and(|bound_body| bound_body != body) {
+            return Err(box_err!("Prepared request carries a checksum or signature over a different body"));
+        }
+        Self::write_head_and_body(stream, &prepared.head, body, &prepared.headers, &prepared.options).await?;
+        Self::read_response(stream, &prepared.options, &prepared.method, Some(&prepared.headers)).await
+    }
+
+    // Public method to send an HTTP request and return the HTTP response
+    pub async fn request(stream: &mut Box<dyn AsyncConnection>, request: &Request<RequestBody>) -> SimpleResult<Response<ResponseBody>> {
+        Self::request_with_options(stream, request, &RequestOptions::default()).await
+    }
+
+    // Sends an HTTP request and returns the HTTP response, applying the given options
+    pub async fn request_with_options(stream: &mut Box<dyn AsyncConnection>, request: &Request<RequestBody>, options: &RequestOptions) -> SimpleResult<Response<ResponseBody>> {
+        if request::expects_continue(request.headers()) && !request.body().is_empty() && !options.expect_continue_timeout.is_zero() {
+            return Self::request_expecting_continue(stream, request, options).await;
+        }
+        let (serialized_request, sent_head) = error::with_stage(RequestStage::Prepare, Self::serialize_for_write(request, options))?;
+        log::debug!(method = request.method().as_str(), uri:% = request.uri(), bytes = serialized_request.len() + request.body().len(); "serialized_request = {serialized_request}");
+
+        // As in request_file, the upload is raced against a final response arriving, since a server answering before
+        // the body is in (a 413 or 401, say) may stop reading it and the write would block once the buffers fill
+        let (mut reader, mut writer) = io::split(&mut *stream);
+        let mut early = Vec::new();
+        let write = async {
+            Self::write_head_and_body(&mut writer, serialized_request.as_bytes(), request.body(), request.headers(), options).await?;
+            SimpleResult::Ok(false)
+        };
+        let response_arrived = async {
+            Self::final_response_started(&mut reader, &mut early, options.max_header_section_size).await?;
+            SimpleResult::Ok(true)
+        };
+        let upload_aborted = error::with_stage(RequestStage::Write, future::or(write, response_arrived).await)?;
+        if upload_aborted {
+            log::debug!("server responded before the {} byte request body was sent, aborting upload", request.body().len());
+        }
+
+        let mut reader = BufReader::new(io::Cursor::new(early).chain(reader));
+        let read = async {
+            let mut response = Self::read_response_from(&mut reader, options, request.method(), Some(request.headers())).await?;
+            Self::apply_trailing_data(reader.buffer(), &mut response, options);
+            let (parts, body) = response.into_parts();
+            SimpleResult::Ok(Response::from_parts(parts, body?))
+        };
+        let mut response = error::with_stage(RequestStage::Read, read.await)?;
+        if upload_aborted {
+            response.extensions_mut().insert(UploadAborted);
+        }
+        if let Some(sent_head) = sent_head {
+            response.extensions_mut().insert(SentRequest(sent_head));
+        }
+        Ok(response)
+    }
+
+    // Reads whatever the server sends while an upload is still going out, returning once a final response has
+    // started. Interim responses (a 100 Continue sent mid-body, 103 Early Hints) are dropped and the upload carries
+    // on past them. The bytes read are left in `early`, to be read ahead of the rest of the response.
+    async fn final_response_started<R>(reader: &mut R, early: &mut Vec<u8>, max_length: usize) -> SimpleResult<()>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut buffer = [0u8; 1024];
+        loop {
+            let read = reader.read(&mut buffer).await?;
+            early.extend_from_slice(&buffer[..read]);
+            // A closed connection or an oversized head is left for the response reader to report
+            if read == 0 || early.len() > max_length || response::final_status_arrived(early) {
+                return Ok(());
+            }
+        }
+    }
+
+    // Sends the head of an `Expect: 100-continue` request and holds the body back until the server answers or
+    // options.expect_continue_timeout passes. A 100 Continue, or silence from a server that ignores Expect, lets
+    // the body go; a final response arriving first (e.g. 401 or 417) means the server won't read the body, so it
+    // is returned without sending it.
+    async fn request_expecting_continue(stream: &mut Box<dyn AsyncConnection>, request: &Request<RequestBody>, options: &RequestOptions) -> SimpleResult<Response<ResponseBody>> {
+        let (serialized_request, sent_head) = error::with_stage(RequestStage::Prepare, Self::serialize_for_write(request, options))?;
+        log::debug!(method = request.method().as_str(), uri:% = request.uri(), bytes = serialized_request.len(); "serialized_request = {serialized_request}");
+        let write_head = async {
+            stream.write_all(serialized_request.as_bytes()).await?;
+            stream.flush().await?;
+            Ok(())
+        };
+        error::with_stage(RequestStage::Write, write_head.await)?;
+
+        let mut reader = BufReader::new(&mut *stream);
+        let wait = options.expect_continue_timeout;
+        let early_response = loop {
+            let answered = future::or(async { reader.fill_buf().await.map(|_| true) }, async {
+                options.clock.sleep(wait).await;
+                Ok(false)
+            })
+            .await;
+            if !error::with_stage(RequestStage::Read, answered.map_err(Into::into))? {
+                log::debug!("no answer to Expect: 100-continue after {wait:?}, sending the body anyway");
+                break None;
+            }
+            let head = error::with_stage(RequestStage::Read, Self::read_response_head(&mut reader, options).await)?;
+            if head.1 == StatusCode::CONTINUE {
+                break None;
+            }
+            // Other interim responses, like 103 Early Hints, don't answer the Expect, so keep waiting
+            if response::is_interim(head.1) {
+                log::debug!("skipping interim {} response", head.1);
+                continue;
+            }
+            log::debug!("server answered {} before the request body was sent", head.1);
+            break Some(Self::response_from_head(&mut reader, options, request.method(), Some(request.headers()), head).await?);
+        };
+        let mut response = match early_response {
+            Some(response) => response,
+            None => {
+                let write_body = async {
+                    reader.get_mut().write_all(request.body()).await?;
+                    reader.get_mut().flush().await?;
+                    Ok(())
+                };
+                error::with_stage(RequestStage::Write, write_body.await)?;
+                error::with_stage(RequestStage::Read, Self::read_response_from(&mut reader, options, request.method(), Some(request.headers())).await)?
+            }
+        };
+        Self::apply_trailing_data(reader.buffer(), &mut response, options);
+
+        let (mut parts, body) = response.into_parts();
+        if let Some(sent_head) = sent_head {
+            parts.extensions.insert(SentRequest(sent_head));
+        }
+        Ok(Response::from_parts(parts, error::with_stage(RequestStage::Read, body)?))
+    }
+
+    // Writes `raw_request` to the stream verbatim and reads the response normally, e.g. to replay captured
+    // traffic or send deliberately malformed requests. Nothing is validated and no headers are added, so the
+    // bytes have to be a complete request. Since the method is unknown, a response to HEAD would be misread.
+    pub async fn send_raw(stream: &mut Box<dyn AsyncConnection>, raw_request: &[u8]) -> SimpleResult<Response<ResponseBody>> {
+        log::debug!("raw_request = {raw_request:02x?}");
+        stream.write_all(raw_request).await?;
+        stream.flush().await?;
+        Self::read_response(stream, &RequestOptions::default(), &Method::GET, None).await
+    }
+
+    // Sends the request and streams the body in, calling `progress(bytes_so_far, total)` as each piece arrives.
+    // `total` is the Content-Length when the server sent one. Returning ControlFlow::Break from the callback
+    // aborts the download, closing the connection and failing with a Cancelled error.
+    pub async fn request_with_progress<F>(stream: &mut Box<dyn AsyncConnection>, request: &Request<RequestBody>, mut progress: F) -> SimpleResult<Response<ResponseBody>>
+    where
+        F: FnMut(u64, Option<u64>) -> ControlFlow<()>,
+    {
+        let options = RequestOptions::default();
+        Self::write_request(stream, request, &options).await?;
+
+        let mut reader = BufReader::new(&mut *stream);
+        let (response_version, response_status, response_headers) = Self::read_final_response_head(&mut reader, &options).await?;
+        let mut response_body = vec![];
+        if response::response_has_body(request.method(), response_status) {
+            let mut body = BodyStream::new(reader, &response_headers, options.max_chunk_line_length)?;
+            while let Some(chunk) = body.next_chunk().await? {
+                response_body.extend_from_slice(&chunk);
+                if progress(body.bytes_read(), body.content_length()).is_break() {
+                    log::debug!("download aborted after {} bytes", body.bytes_read());
+                    drop(body);
+                    stream.close().await?;
+                    return Err(HttpClientError::Cancelled.into());
+                }
+            }
+        }
+
+        let mut response = Response::builder()
+            .status(response_status)
+            .version(response_version)
+            .body(response_body)?;
+        *response.headers_mut() = response_headers;
+        Ok(response)
+    }
+
+    // Uploads the file at `path` as the request body, streaming it in bounded chunks rather than loading it into
+    // memory. Content-Length comes from the file's size when opened; should the file shrink during the upload
+    // this fails rather than sending a short body, and bytes appended meanwhile are not sent.
+    pub async fn request_file(stream: &mut Box<dyn AsyncConnection>, request: &Request<()>, path: &Path) -> SimpleResult<Response<ResponseBody>> {
+        let mut file = async_fs::File::open(path).await?;
+        let file_length = file.metadata().await?.len();
+
+        // The file's size replaces whatever framing the request carried
+        let mut head = request.clone();
+        head.headers_mut().remove(header::TRANSFER_ENCODING);
+        head.headers_mut().insert(header::CONTENT_LENGTH, HeaderValue::from(file_length));
+        let options = RequestOptions::default();
+        let serialized_request = request::serialize_http_request(&head, None, &options)?;
+        log::debug!(method = head.method().as_str(), uri:% = head.uri(), bytes = serialized_request.len() as u64 + file_length; "serialized_request = {serialized_request}");
+        stream.write_all(serialized_request.as_bytes()).await?;
+
+        // Servers may answer before the body is in (a 413 or 401, say) and stop reading it, so each chunk is raced
+        // against a final response arriving. Writing on regardless would deadlock once the socket buffers fill.
+        let (mut reader, mut writer) = io::split(&mut *stream);
+        let mut early = Vec::new();
+        let mut remaining = file_length;
+        let mut buffer = vec![0u8; request::UPLOAD_CHUNK_SIZE];
+        while remaining > 0 {
+            let len = remaining.min(buffer.len() as u64) as usize;
+            let read = file.read(&mut buffer[..len]).await?;
+            if read == 0 {
+                return Err(format!("{} shrank during upload, {remaining} of {file_length} bytes were not sent", path.display()).into());
+            }
+            let write_chunk = async {
+                writer.write_all(&buffer[..read]).await?;
+                SimpleResult::Ok(false)
+            };
+            let response_arrived = async {
+                Self::final_response_started(&mut reader, &mut early, options.max_header_section_size).await?;
+                SimpleResult::Ok(true)
+            };
+            if future::or(write_chunk, response_arrived).await? {
+                log::debug!("server responded after {} of {file_length} bytes, aborting upload", file_length - remaining);
+                break;
+            }
+            remaining -= read as u64;
+        }
+        if remaining == 0 {
+            writer.flush().await?;
+        }
+
+        let mut reader = BufReader::new(io::Cursor::new(early).chain(reader));
+        let (mut parts, body) = Self::read_response_from(&mut reader, &options, head.method(), Some(head.headers())).await?.into_parts();
+        if remaining > 0 {
+            parts.extensions.insert(UploadAborted);
+        }
+        Ok(Response::from_parts(parts, body?))
+    }
+
+    // Sends the request and reads only the status and headers, discarding the framed body so the
+    // connection is left at the next response boundary and can be reused
+    pub async fn request_discard_body(stream: &mut Box<dyn AsyncConnection>, request: &Request<RequestBody>) -> SimpleResult<Response<()>> {
+        Self::write_request(stream, request, &RequestOptions::default()).await?;
+
+        // Read the head, then skip over the body
         let mut reader = BufReader::new(stream);
-        let response_status_line = response::read_response_status_line(&mut reader).await?;
-        log::debug!("response_status_line = {response_status_line}");
-        let (response_version, response_status) = response::parse_response_status_line(&response_status_line)?;
-        let response_headers = response::read_response_headers(&mut reader).await?;
-        log::debug!("response_headers = {response_headers:?}");
-        let response_body = if response_status == StatusCode::NO_CONTENT || response_status == StatusCode::NOT_MODIFIED {
-            vec![]
+        let (response_version, response_status, response_headers) = Self::read_final_response_head(&mut reader, &RequestOptions::default()).await?;
+        let has_body = response::response_has_body(request.method(), response_status);
+        if has_body {
+            let discarded = response::discard_response_body(&mut reader, &response_headers).await?;
+            log::debug!("discarded {discarded} response body bytes");
+        }
+
+        let mut response = Response::builder()
+            .status(response_status)
+            .version(response_version)
+            .body(())?;
+        *response.headers_mut() = response_headers;
+        Ok(response)
+    }
+
+    // Sends the request and reads the body into memory until it grows past `spill_threshold` bytes,
+    // after which it is written to a temporary file that is removed when the body is dropped
+    pub async fn request_spooled(stream: &mut Box<dyn AsyncConnection>, request: &Request<RequestBody>, spill_threshold: usize) -> SimpleResult<Response<SpooledBody>> {
+        Self::write_request(stream, request, &RequestOptions::default()).await?;
+
+        // Read the head, then spool the body
+        let mut reader = BufReader::new(stream);
+        let (response_version, response_status, response_headers) = Self::read_final_response_head(&mut reader, &RequestOptions::default()).await?;
+        let has_body = response::response_has_body(request.method(), response_status);
+        let response_body = if has_body {
+            spooled_body::read_response_body_spooled(&mut reader, &response_headers, spill_threshold).await?
         } else {
-            response::read_response_body(&mut reader, &response_headers).await?
+            SpooledBody::Memory(vec![])
         };
-        log::debug!("response_body = {response_body:02x?}");
+        log::debug!("response_body = {response_body:?}");
 
-        // Convert to HTTP crate response
-        let mut response: Response<ResponseBody> = Response::builder()
+        let mut response = Response::builder()
             .status(response_status)
             .version(response_version)
             .body(response_body)?;
+        *response.headers_mut() = response_headers;
+        Ok(response)
+    }
+
+    // Sends the request body with chunked transfer coding, followed by trailer fields computed from the body.
+    // Every trailer has to be announced up front in `trailer_names`, which becomes the Trailer header.
+    pub async fn request_with_trailers<F>(stream: &mut Box<dyn AsyncConnection>, request: &mut Request<RequestBody>, trailer_names: &[HeaderName], trailers: F) -> SimpleResult<Response<ResponseBody>>
+    where
+        F: FnOnce(&[u8]) -> SimpleResult<HeaderMap>,
+    {
+        // Chunked framing replaces any declared length
+        let headers = request.headers_mut();
+        headers.remove(header::CONTENT_LENGTH);
+        headers.insert(header::TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
+        if !trailer_names.is_empty() {
+            let announced = trailer_names.iter().map(HeaderName::as_str).collect::<Vec<_>>().join(", ");
+            headers.insert(header::TRAILER, HeaderValue::from_str(&announced)?);
+        }
+
+        // Write the HTTP request head to the stream
+        let serialized_request = request::serialize_http_request(request, None, &RequestOptions::default())?;
+        log::debug!(method = request.method().as_str(), uri:% = request.uri(); "serialized_request = {serialized_request}");
+        stream.write_all(serialized_request.as_bytes()).await?;
+
+        // Compute the trailers and make sure they were all announced
+        let request_trailers = trailers(request.body())?;
+        if let Some(name) = request_trailers.keys().find(|name| !trailer_names.contains(name)) {
+            return Err(format!("Trailer {name} was not announced").into());
+        }
+        log::debug!("request_trailers = {request_trailers:?}");
+        request::write_chunked_body(stream, request.body(), &request_trailers).await?;
+
+        Self::read_response(stream, &RequestOptions::default(), request.method(), Some(request.headers())).await
+    }
+
+    // Sends the request and, if the server answers with `text/event-stream`, returns the response head along
+    // with the events decoded from the body as they arrive. The connection is owned by the event stream.
+    pub async fn request_event_stream(mut stream: Box<dyn AsyncConnection>, request: &Request<RequestBody>) -> SimpleResult<(Response<()>, EventStream<Box<dyn AsyncConnection>>)> {
+        let options = RequestOptions::default();
+        Self::write_request(&mut stream, request, &options).await?;
+
+        let mut reader = BufReader::new(stream);
+        let (response_version, response_status, response_headers) = Self::read_final_response_head(&mut reader, &options).await?;
+        if !sse::is_event_stream(&response_headers) {
+            return Err(format!("Expected an event stream, got {:?} with status {response_status}", response_headers.get(header::CONTENT_TYPE)).into());
+        }
+        let body = BodyStream::new(reader, &response_headers, options.max_chunk_line_length)?;
+
+        let mut response = Response::builder()
+            .status(response_status)
+            .version(response_version)
+            .body(())?;
+        *response.headers_mut() = response_headers;
+        Ok((response, EventStream::new(body)))
+    }
+
+    // Sends a request carrying an Upgrade header (see `request_upgrade` and `request_h2c_upgrade`) and, once the
+    // server answers 101, hands back the connection for the new protocol. Any other status is an error.
+    pub async fn upgrade(mut stream: Box<dyn AsyncConnection>, request: &Request<RequestBody>) -> SimpleResult<(Response<()>, Upgraded<Box<dyn AsyncConnection>>)> {
+        let protocol = request.headers().get(header::UPGRADE).ok_or("Request has no Upgrade header")?.clone();
+        Self::write_request(&mut stream, request, &RequestOptions::default()).await?;
+
+        let mut reader = BufReader::new(stream);
+        let (response_version, response_status, response_headers) = Self::read_final_response_head(&mut reader, &RequestOptions::default()).await?;
+        if response_status != StatusCode::SWITCHING_PROTOCOLS {
+            return Err(format!("Server ignored the upgrade to {protocol:?} and responded {response_status}").into());
+        }
+        let upgraded_to = response_headers.get(header::UPGRADE);
+        if !upgraded_to.is_some_and(|upgraded_to| upgraded_to.as_bytes().eq_ignore_ascii_case(protocol.as_bytes())) {
+            return Err(format!("Server switched to {upgraded_to:?} instead of {protocol:?}").into());
+        }
+
+        let mut response = Response::builder()
+            .status(response_status)
+            .version(response_version)
+            .body(())?;
+        *response.headers_mut() = response_headers;
+        Ok((response, Upgraded::from_reader(reader)))
+    }
+
+    // Reads and parses the status line and headers of a response
+    async fn read_response_head<S>(reader: &mut BufReader<S>, options: &RequestOptions) -> SimpleResult<(Version, StatusCode, HeaderMap)>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let response_status_line = response::read_response_status_line(reader, options.max_status_line_length).await?;
+        log::debug!("response_status_line = {response_status_line}");
+        let (response_version, response_status) = response::parse_response_status_line(&response_status_line)?;
+        let response_headers = response::read_response_headers(reader, options).await?;
+        log::debug!(status = response_status.as_u16(), version:? = response_version; "response_headers = {response_headers:?}");
+        Ok((response_version, response_status, response_headers))
+    }
+
+    // Reads response heads until the final one. Interim 1xx responses (100 Continue, 102 Processing, 103 Early
+    // Hints) only precede the real response; 101 is final since it ends HTTP on the connection.
+    async fn read_final_response_head<S>(reader: &mut BufReader<S>, options: &RequestOptions) -> SimpleResult<(Version, StatusCode, HeaderMap)>
+    where
+        S: AsyncRead + Unpin,
+    {
+        loop {
+            let head = Self::read_response_head(reader, options).await?;
+            if !response::is_interim(head.1) {
+                return Ok(head);
+            }
+            log::debug!("skipping interim {} response", head.1);
+        }
+    }
+
+    // Reads and parses the response to a request that has already been written to the stream.
+    // `request_headers` are the headers that request was sent with, when known.
+    async fn read_response(stream: &mut Box<dyn AsyncConnection>, options: &RequestOptions, method: &Method, request_headers: Option<&HeaderMap>) -> SimpleResult<Response<ResponseBody>> {
+        let (parts, body) = Self::read_response_partial(stream, options, method, request_headers).await?.into_parts();
+        Ok(Response::from_parts(parts, body?))
+    }
+
+    // Sends the request and returns the response even when its body can't be read, e.g. because the connection
+    // dropped midway. Only a failure before the status and headers were parsed fails the whole call; a body read
+    // error is kept in the response body instead, next to the status that is often enough to act on.
+    pub async fn request_partial(stream: &mut Box<dyn AsyncConnection>, request: &Request<RequestBody>, options: &RequestOptions) -> SimpleResult<Response<SimpleResult<ResponseBody>>> {
+        Self::write_request(stream, request, options).await?;
+        Self::read_response_partial(stream, options, request.method(), Some(request.headers())).await
+    }
+
+    // Like read_response, but a body read error is returned as the response body instead of discarding the head
+    async fn read_response_partial(
+        stream: &mut Box<dyn AsyncConnection>,
+        options: &RequestOptions,
+        method: &Method,
+        request_headers: Option<&HeaderMap>,
+    ) -> SimpleResult<Response<SimpleResult<ResponseBody>>> {
+        let mut reader = BufReader::new(stream);
+        let mut response = Self::read_response_from(&mut reader, options, method, request_headers).await?;
+        Self::apply_trailing_data(reader.buffer(), &mut response, options);
+        Ok(response)
+    }
+
+    // Applies options.trailing_data to whatever was still buffered after the end of the response
+    fn apply_trailing_data(trailing_data: &[u8], response: &mut Response<SimpleResult<ResponseBody>>, options: &RequestOptions) {
+        if response.body().is_ok() && !trailing_data.is_empty() {
+            match options.trailing_data {
+                TrailingDataPolicy::Ignore => log::debug!("ignoring {} bytes after the end of the response", trailing_data.len()),
+                TrailingDataPolicy::Error => {
+                    *response.body_mut() = Err(format!("Server sent {} bytes after the end of the response", trailing_data.len()).into());
+                }
+                TrailingDataPolicy::Preserve => {
+                    let trailing_data = TrailingData(trailing_data.to_vec());
+                    response.extensions_mut().insert(trailing_data);
+                }
+            }
+        }
+    }
+
+    // Sends the request and also returns the response exactly as it came off the wire (status line, headers and
+    // body, before any parsing), e.g. for a caching proxy that replays byte-identical responses. Opt-in since
+    // the body is held twice.
+    pub async fn request_with_raw_response(stream: &mut Box<dyn AsyncConnection>, request: &Request<RequestBody>, options: &RequestOptions) -> SimpleResult<(Response<ResponseBody>, Vec<u8>)> {
+        Self::write_request(stream, request, options).await?;
+
+        let mut reader = BufReader::new(TeeReader::new(&mut *stream));
+        let response = Self::read_response_from(&mut reader, options, request.method(), Some(request.headers())).await?;
+        // Whatever the reader buffered past the end of the response isn't part of it
+        let unread = reader.buffer().len();
+        let mut raw_response = reader.into_inner().into_captured();
+        raw_response.truncate(raw_response.len() - unread);
+
+        let (parts, body) = response.into_parts();
+        Ok((Response::from_parts(parts, body?), raw_response))
+    }
+
+    // Reads a response from the reader, keeping a body read error in the response body
+    async fn read_response_from<S>(
+        reader: &mut BufReader<S>,
+        options: &RequestOptions,
+        method: &Method,
+        request_headers: Option<&HeaderMap>,
+    ) -> SimpleResult<Response<SimpleResult<ResponseBody>>>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let head = Self::read_final_response_head(reader, options).await?;
+        Self::response_from_head(reader, options, method, request_headers, head).await
+    }
+
+    // Reads the body that follows an already parsed response head and assembles the response
+    async fn response_from_head<S>(
+        reader: &mut BufReader<S>,
+        options: &RequestOptions,
+        method: &Method,
+        request_headers: Option<&HeaderMap>,
+        (response_version, response_status, response_headers): (Version, StatusCode, HeaderMap),
+    ) -> SimpleResult<Response<SimpleResult<ResponseBody>>>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let body_result = Self::read_body(reader, options, method, request_headers, response_version, response_status, &response_headers).await;
+
+        // Convert to HTTP crate response
+        let mut response = Response::builder()
+            .status(response_status)
+            .version(response_version)
+            .body(Ok(vec![]))?;
 
         // Copy response headers to response
         *response.headers_mut() = response_headers;
 
+        match body_result {
+            Ok((response_body, response_framing, response_trailers)) => {
+                log::debug!(framing:? = response_framing; "response_framing = {response_framing:?}");
+                log::debug!(bytes = response_body.len(); "response_body = {response_body:02x?}");
+                *response.body_mut() = Ok(response_body);
+
+                // Record how the body was framed, for diagnosing servers and deciding whether the connection is reusable
+                response.extensions_mut().insert(response_framing);
+
+                // Keep trailers apart from the headers so neither clobbers the other
+                if !response_trailers.is_empty() {
+                    log::debug!("response_trailers = {response_trailers:?}");
+                    response.extensions_mut().insert(Trailers(response_trailers));
+                }
+            }
+            Err(err) => {
+                log::debug!("failed to read response body: {err}");
+                *response.body_mut() = Err(err);
+            }
+        }
+
         // log
         log::debug!("response = {response:02x?}");
 
@@ -63,6 +871,136 @@ impl HttpClient {
         Ok(response)
     }
 
+    // Reads the body that follows a parsed response head, with how it was framed and any trailers
+    async fn read_body<S>(
+        reader: &mut BufReader<S>,
+        options: &RequestOptions,
+        method: &Method,
+        request_headers: Option<&HeaderMap>,
+        response_version: Version,
+        response_status: StatusCode,
+        response_headers: &HeaderMap,
+    ) -> SimpleResult<(ResponseBody, BodyFraming, HeaderMap)>
+    where
+        S: AsyncRead + Unpin,
+    {
+        // Responses to HEAD, 1xx, 204 and 304 never have a body, whatever their headers say. A HEAD response's
+        // Content-Length describes the resource, not bytes that follow, and waiting for them would hang.
+        if !response::response_has_body(method, response_status) {
+            // A 101 hands the connection over to the upgraded protocol
+            if response_status == StatusCode::SWITCHING_PROTOCOLS {
+                log::debug!("switching protocols, upgrade = {:?}", response_headers.get(header::UPGRADE));
+            }
+            // Some servers (and CDNs) send a nonzero Content-Length with 204 and 304 anyway
+            if response_status == StatusCode::NO_CONTENT || response_status == StatusCode::NOT_MODIFIED {
+                if let Some(content_length) = response_headers.get(header::CONTENT_LENGTH).filter(|value| *value != "0") {
+                    log::warn!("ignoring Content-Length {content_length:?} on a {response_status} response");
+                }
+            }
+            return Ok((vec![], BodyFraming::None, HeaderMap::new()));
+        }
+        // A body with no declared length runs until the server closes the connection (RFC 9112 section 6.3). A
+        // caller that explicitly asked to keep the connection alive, e.g. to reuse it, would hang waiting for that
+        // close, so without an idle timeout the body is refused rather than read or returned empty.
+        let keep_alive_requested = request_headers.is_some_and(|request_headers| {
+            response::connection_has_token(request_headers, "keep-alive") && response::is_persistent(request_headers, response_version, response_headers)
+        });
+        if keep_alive_requested && !response::is_framed(response_headers)? && options.unframed_body_idle_timeout.is_none() {
+            log::warn!("response has no declared length on a persistent connection");
+            return Err(HttpClientError::UnframedBodyNotAllowed.into());
+        }
+        response::read_response_body(reader, response_headers, options).await
+    }
+
+    // Opens a fresh connection for the request and sends it
+    async fn connect_and_request(mut request: Request<RequestBody>) -> SimpleResult<Response<ResponseBody>> {
+        request::close_connection_by_default(&mut request);
+        let mut stream = AsyncConnectionFactory::connect(&request).await?;
+        let response = Self::request(&mut stream, &request).await;
+        Self::close(&mut stream).await;
+        response
+    }
+
+    // Opens up to `count` connections to the origin of `uri` at once, DNS, TCP and the TLS handshake included, so
+    // only the request round trip is left when they are used. There is no pool to park them in, so they are handed
+    // back for the caller to send requests on. Connections that fail to open are logged and left out.
+    pub async fn preconnect(uri: &str, count: usize, options: &ConnectOptions) -> SimpleResult<Vec<Box<dyn AsyncConnection>>> {
+        let request = Request::builder().uri(uri).body(())?;
+        let mut connecting: Vec<ConnectFuture<'_>> =
+            (0..count).map(|_| Box::pin(AsyncConnectionFactory::connect_with_options(&request, options)) as _).collect();
+        let mut results: Vec<Option<SimpleResult<Box<dyn AsyncConnection>>>> = connecting.iter().map(|_| None).collect();
+
+        future::poll_fn(|cx| {
+            let mut any_pending = false;
+            for (future, result) in connecting.iter_mut().zip(results.iter_mut()).filter(|(_, result)| result.is_none()) {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(connection) => *result = Some(connection),
+                    Poll::Pending => any_pending = true,
+                }
+            }
+            if any_pending {
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+        .await;
+
+        let mut connections = Vec::with_capacity(count);
+        for result in results.into_iter().flatten() {
+            match result {
+                Ok(connection) => connections.push(connection),
+                Err(err) => log::warn!("failed to preconnect to {uri}: {err}"),
+            }
+        }
+        Ok(connections)
+    }
+
+    // Sends every request on its own connection with at most `max_concurrency` in flight at once.
+    // Results come back in input order, and a failed request does not affect the others.
+    pub async fn request_many(requests: Vec<Request<RequestBody>>, max_concurrency: usize) -> Vec<SimpleResult<Response<ResponseBody>>> {
+        let max_concurrency = max_concurrency.max(1);
+        let mut results: Vec<Option<SimpleResult<Response<ResponseBody>>>> = requests.iter().map(|_| None).collect();
+        let mut pending = requests.into_iter().enumerate();
+        let mut in_flight: Vec<(usize, ResponseFuture)> = Vec::new();
+
+        future::poll_fn(|cx| loop {
+            // Top up the in-flight set from the pending requests
+            while in_flight.len() < max_concurrency {
+                match pending.next() {
+                    Some((index, request)) => in_flight.push((index, Box::pin(Self::connect_and_request(request)))),
+                    None => break,
+                }
+            }
+
+            // Poll everything in flight, collecting whatever finished
+            let mut finished_any = false;
+            let mut slot = 0;
+            while slot < in_flight.len() {
+                if let Poll::Ready(result) = in_flight[slot].1.as_mut().poll(cx) {
+                    let (index, _) = in_flight.swap_remove(slot);
+                    results[index] = Some(result);
+                    finished_any = true;
+                } else {
+                    slot += 1;
+                }
+            }
+
+            if in_flight.is_empty() {
+                return Poll::Ready(());
+            }
+            if !finished_any {
+                return Poll::Pending;
+            }
+        })
+        .await;
+
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err(box_err!("Request was never sent"))))
+            .collect()
+    }
+
     pub async fn json_request<RequestBody, ResponseBody>(url: &str, request_body: &RequestBody) -> SimpleResult<ResponseBody>
     where 
         RequestBody: miniserde::Serialize, 
@@ -70,27 +1008,303 @@ impl HttpClient {
     {
         // build request
         let uri: Uri = url.parse()?;
-        let host = uri.host().unwrap_or("").to_string();
         let stringified_request_body = miniserde::json::to_string(&request_body);
-        let request_body_bytes = stringified_request_body.as_bytes().to_vec();
-        let request = Request::builder()
+        let request_body_bytes = stringified_request_body.into_bytes();
+        let mut request = Request::builder()
             .method("POST")
             .uri(uri)
-            .header("Host", host)
             .header("Content-Type", "application/json")
-            .header("Content-Length", format!("{}", stringified_request_body.len()))
             .body(request_body_bytes)?;
+        request::close_connection_by_default(&mut request);
 
         // make request
         let mut stream = AsyncConnectionFactory::connect(&request).await?;
-        let response = Self::request(&mut stream, &request).await?;
+        let response = Self::request(&mut stream, &request).await;
+        Self::close(&mut stream).await;
+        let response = response?;
 
         // parse response
-        let response_body_bytes = response.body().to_owned();
-        let stringified_response_body = String::from_utf8(response_body_bytes)?;
-        let response_body: ResponseBody = miniserde::json::from_str(&stringified_response_body)?;
+        response::response_json(response)
+    }
+
+    // Downloads `url` into `sink`, resuming with ranged GETs when a transfer breaks off. A HEAD request first learns
+    // the size, range support and ETag. Resumes send If-Range, so if the resource changed in between the server
+    // sends it whole and the download fails, since the sink already holds bytes of the old version. Servers that
+    // ignore Range are handled by skipping the bytes already written. Makes at most `max_attempts` GETs and
+    // returns the number of bytes written.
+    pub async fn download<W>(url: &str, sink: &mut W, max_attempts: usize) -> SimpleResult<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let uri: Uri = url.parse()?;
+        let mut head_request = Request::builder().method(Method::HEAD).uri(uri.clone()).body(vec![])?;
+        request::close_connection_by_default(&mut head_request);
+        let mut stream = AsyncConnectionFactory::connect(&head_request).await?;
+        let head_response = Self::request_discard_body(&mut stream, &head_request).await;
+        Self::close(&mut stream).await;
+        let head_response = head_response?;
+        if !head_response.status().is_success() {
+            return Err(format!("HEAD {url} failed with status {}", head_response.status()).into());
+        }
+        let target = DownloadTarget::from_headers(head_response.headers());
+        log::debug!("download target = {target:?}");
+
+        let mut written = 0;
+        let mut last_err = None;
+        for attempt in 1..=max_attempts.max(1) {
+            match Self::download_attempt(&uri, &target, sink, &mut written).await? {
+                Ok(()) => return Ok(written),
+                Err(err) => {
+                    log::warn!("download attempt {attempt} for {url} broke off after {written} bytes: {err}");
+                    last_err = Some(err);
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| box_err!("Download was never attempted")))
+    }
+
+    // One GET of a download, continuing from `written`. The outer error is fatal; the inner one means the transfer
+    // broke off and can be resumed.
+    async fn download_attempt<W>(uri: &Uri, target: &DownloadTarget, sink: &mut W, written: &mut u64) -> SimpleResult<SimpleResult<()>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut request_builder = Request::builder().method(Method::GET).uri(uri.clone());
+        if *written > 0 && target.accept_ranges.is_resumable() {
+            request_builder = request_builder.header(header::RANGE, format!("bytes={written}-"));
+            if let Some(etag) = &target.etag {
+                request_builder = request_builder.header(header::IF_RANGE, etag.clone());
+            }
+        }
+        let mut request = request_builder.body(vec![])?;
+        request::close_connection_by_default(&mut request);
+
+        let head = async {
+            let mut stream = AsyncConnectionFactory::connect(&request).await?;
+            Self::write_request(&mut stream, &request, &RequestOptions::default()).await?;
+            let mut reader = BufReader::new(stream);
+            let (_, status, headers) = Self::read_final_response_head(&mut reader, &RequestOptions::default()).await?;
+            SimpleResult::Ok((reader, status, headers))
+        };
+        let (reader, status, headers) = match head.await {
+            Ok(head) => head,
+            Err(err) => return Ok(Err(err)),
+        };
+
+        // Work out how much of what the server sends was already written
+        let mut skip = match status {
+            StatusCode::PARTIAL_CONTENT => {
+                let start = download::content_range_start(&headers).ok_or("206 response without a usable Content-Range")?;
+                if start > *written {
+                    return Err(format!("Server resumed at byte {start}, past the {written} bytes written").into());
+                }
+                *written - start
+            }
+            StatusCode::OK => {
+                let etag = headers.get(header::ETAG);
+                if *written > 0 && target.etag.is_some() && etag != target.etag.as_ref() {
+                    return Err(format!("Resource changed during download (ETag {:?} became {etag:?})", target.etag).into());
+                }
+                // The server ignored Range, so the body starts over
+                *written
+            }
+            status => return Err(format!("Download failed with status {status}").into()),
+        };
+
+        let mut body = BodyStream::new(reader, &headers, DEFAULT_MAX_CHUNK_LINE_LENGTH)?;
+        loop {
+            let chunk = match body.next_chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(err) => return Ok(Err(err)),
+            };
+            let skipped = skip.min(chunk.len() as u64) as usize;
+            skip -= skipped as u64;
+            sink.write_all(&chunk[skipped..]).await?;
+            *written += (chunk.len() - skipped) as u64;
+        }
+        sink.flush().await?;
+        Self::close(&mut body.into_inner().into_inner()).await;
+
+        match target.content_length {
+            Some(total) if *written < total => Ok(Err(format!("Transfer ended after {written} of {total} bytes").into())),
+            _ => Ok(Ok(())),
+        }
+    }
+
+    // Issues a CORS preflight (OPTIONS) and returns the Access-Control-Allow-* headers it granted.
+    // Browsers send preflights themselves and won't let scripts set these headers, so this is for
+    // probing an endpoint's CORS configuration from a native client.
+    pub async fn cors_preflight(url: &str, origin: &str, method: &Method, request_headers: &[&str]) -> SimpleResult<CorsPolicy> {
+        // build request
+        let uri: Uri = url.parse()?;
+        let mut request_builder = Request::builder()
+            .method(Method::OPTIONS)
+            .uri(uri)
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", method.as_str());
+        if !request_headers.is_empty() {
+            request_builder = request_builder.header("Access-Control-Request-Headers", request_headers.join(", "));
+        }
+        let mut request = request_builder.body(vec![])?;
+        request::close_connection_by_default(&mut request);
+
+        // make request
+        let mut stream = AsyncConnectionFactory::connect(&request).await?;
+        let response = Self::request(&mut stream, &request).await;
+        Self::close(&mut stream).await;
+        let response = response?;
+        if !response.status().is_success() {
+            return Err(format!("CORS preflight failed with status {}", response.status()).into());
+        }
 
         // return
-        Ok(response_body)
+        Ok(CorsPolicy::from_headers(response.headers()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Context;
+    use std::time::Duration;
+
+    use futures_lite::io::Cursor;
+
+    use super::*;
+
+    // A server that answers according to how much of the request body it has received, counting body bytes as
+    // the '#'s written, and closes once its script runs out. Writes are taken a few bytes at a time with a yield
+    // in between, so responses can arrive while the upload is still going.
+    struct ScriptedConnection {
+        script: Vec<(usize, &'static [u8])>,
+        sending: Cursor<Vec<u8>>,
+        body_received: usize,
+        yield_next: bool,
+    }
+
+    impl ScriptedConnection {
+        fn new(script: Vec<(usize, &'static [u8])>) -> Self {
+            Self {
+                script,
+                sending: Cursor::new(Vec::new()),
+                body_received: 0,
+                yield_next: false,
+            }
+        }
+    }
+
+    impl AsyncRead for ScriptedConnection {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+            if self.sending.position() == self.sending.get_ref().len() as u64 {
+                match self.script.first() {
+                    Some(&(after, response)) if after <= self.body_received => {
+                        self.script.remove(0);
+                        self.sending = Cursor::new(response.to_vec());
+                    }
+                    None => return Poll::Ready(Ok(0)),
+                    _ => {
+                        cx.waker().wake_by_ref();
+                        return Poll::Pending;
+                    }
+                }
+            }
+            Pin::new(&mut self.sending).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for ScriptedConnection {
+        fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+            if self.yield_next {
+                self.yield_next = false;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.yield_next = true;
+            let len = buf.len().min(16);
+            self.body_received += buf[..len].iter().filter(|&&byte| byte == b'#').count();
+            Poll::Ready(Ok(len))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncConnection for ScriptedConnection {
+        fn is_encrypted(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn final_response_mid_upload_aborts_it() {
+        let mut stream: Box<dyn AsyncConnection> = Box::new(ScriptedConnection::new(vec![(32, &b"HTTP/1.1 413 Payload Too Large\r\nContent-Length: 0\r\n\r\n"[..])]));
+        let request = Request::post("http://example.com/").body(vec![b'#'; 4096]).unwrap();
+
+        let response = future::block_on(HttpClient::request_with_options(&mut stream, &request, &RequestOptions::default())).unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(response.extensions().get::<UploadAborted>().is_some());
+        assert!(!connection_reusable(&request, &response));
+    }
+
+    #[test]
+    fn continue_sent_mid_body_does_not_abort_upload() {
+        let script = vec![(32, &b"HTTP/1.1 100 Continue\r\n\r\n"[..]), (4096, &b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"[..])];
+        let mut stream: Box<dyn AsyncConnection> = Box::new(ScriptedConnection::new(script));
+        let request = Request::post("http://example.com/").header(header::EXPECT, "100-continue").body(vec![b'#'; 4096]).unwrap();
+        let options = RequestOptions {
+            expect_continue_timeout: Duration::ZERO,
+            ..RequestOptions::default()
+        };
+
+        let response = future::block_on(HttpClient::request_with_options(&mut stream, &request, &options)).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), b"ok");
+        assert!(response.extensions().get::<UploadAborted>().is_none());
+    }
+
+    #[test]
+    fn not_modified_ignores_content_length_on_kept_alive_connection() {
+        let wire = b"HTTP/1.1 304 Not Modified\r\nContent-Length: 500\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok".to_vec();
+        let mut reader = BufReader::new(Cursor::new(wire));
+        let options = RequestOptions::default();
+        let request_headers = HeaderMap::new();
+
+        let not_modified = future::block_on(HttpClient::read_response_from(&mut reader, &options, &Method::GET, Some(&request_headers))).unwrap();
+        assert_eq!(not_modified.status(), StatusCode::NOT_MODIFIED);
+        assert!(not_modified.body().as_ref().unwrap().is_empty());
+
+        let next = future::block_on(HttpClient::read_response_from(&mut reader, &options, &Method::GET, Some(&request_headers))).unwrap();
+        assert_eq!(next.status(), StatusCode::OK);
+        assert_eq!(next.body().as_ref().unwrap(), b"ok");
+    }
+
+    #[test]
+    fn unframed_body_is_read_until_close() {
+        let mut stream: Box<dyn AsyncConnection> = Box::new(ScriptedConnection::new(vec![(1, &b"HTTP/1.1 200 OK\r\n\r\nhello"[..])]));
+        let request = Request::post("http://example.com/").body(b"#".to_vec()).unwrap();
+
+        let response = future::block_on(HttpClient::request(&mut stream, &request)).unwrap();
+        assert_eq!(response.body(), b"hello");
+        assert_eq!(response.extensions().get::<BodyFraming>(), Some(&BodyFraming::UntilClose));
+    }
+
+    #[test]
+    fn failure_preparing_a_post_is_retry_safe() {
+        let mut stream: Box<dyn AsyncConnection> = Box::new(ScriptedConnection::new(vec![]));
+        let request = Request::post("http://example.com/").body(b"#".to_vec()).unwrap();
+        let options = RequestOptions {
+            before_send: Some(BeforeSend(Arc::new(|_: &mut Request<()>| -> SimpleResult<()> { Err("signing key unavailable".into()) }))),
+            ..RequestOptions::default()
+        };
+
+        let err = future::block_on(HttpClient::request_with_options(&mut stream, &request, &options)).unwrap_err();
+        let stage = err.downcast_ref::<HttpClientError>().and_then(HttpClientError::stage);
+        assert_eq!(stage, Some(RequestStage::Prepare));
+        assert!(stage.unwrap().is_retry_safe(&Method::POST));
     }
 }
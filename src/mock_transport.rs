@@ -0,0 +1,65 @@
+use std::sync::Mutex;
+
+use http::{HeaderMap, HeaderValue, Method, Request, Response, StatusCode, Uri};
+use simple_error::SimpleResult;
+
+use crate::transport::{Transport, TransportFuture};
+
+struct MockRoute {
+    method: Method,
+    uri: Uri,
+    status: StatusCode,
+    headers: HeaderMap<HeaderValue>,
+    body: Vec<u8>,
+}
+
+// Transport answering from pre-registered responses, for testing code built on HttpClient without a network.
+// Routes match on method and full URI; requests are recorded so tests can assert on what was sent.
+#[derive(Default)]
+pub struct MockTransport {
+    routes: Vec<MockRoute>,
+    requests: Mutex<Vec<(Method, Uri)>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registers the response returned for requests matching `method` and `uri`
+    pub fn with_response(mut self, method: Method, uri: &str, status: StatusCode, headers: HeaderMap<HeaderValue>, body: Vec<u8>) -> SimpleResult<Self> {
+        self.routes.push(MockRoute {
+            method,
+            uri: uri.parse()?,
+            status,
+            headers,
+            body,
+        });
+        Ok(self)
+    }
+
+    // Method and URI of every request received so far, in order
+    pub fn requests(&self) -> SimpleResult<Vec<(Method, Uri)>> {
+        Ok(self.requests.lock().map_err(|_| "Mock transport lock poisoned")?.clone())
+    }
+
+    fn respond(&self, request: &Request<Vec<u8>>) -> SimpleResult<Response<Vec<u8>>> {
+        self.requests.lock().map_err(|_| "Mock transport lock poisoned")?.push((request.method().clone(), request.uri().clone()));
+
+        let route = self
+            .routes
+            .iter()
+            .find(|route| route.method == request.method() && route.uri == *request.uri())
+            .ok_or_else(|| format!("No mock response registered for {} {}", request.method(), request.uri()))?;
+
+        let mut response = Response::builder().status(route.status).body(route.body.clone())?;
+        *response.headers_mut() = route.headers.clone();
+        Ok(response)
+    }
+}
+
+impl Transport for MockTransport {
+    fn roundtrip<'a>(&'a self, request: &'a Request<Vec<u8>>) -> TransportFuture<'a> {
+        Box::pin(async move { self.respond(request) })
+    }
+}
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use http::{HeaderMap, Method, Request};
+
+use crate::request_options::RequestOptions;
+
+// A request serialized once and sent many times with HttpClient::send_prepared, so a polling loop doesn't rebuild
+// the same head for every send. The head is frozen: Host, Content-Length, a Date from send_date, a body checksum and
+// whatever before_send changed all keep the values they had when it was prepared. Prepare it again to change any
+// of them. When a body checksum or before_send hook is configured the head may cover the body itself, so the body
+// is bound too and only that exact body can be sent with it. Clones share the serialized head.
+#[derive(Clone, Debug)]
+pub struct PreparedRequest {
+    pub(crate) head: Arc<[u8]>,
+    pub(crate) method: Method,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body_length: usize,
+    // The body a digest or signature in the head was computed over
+    pub(crate) bound_body: Option<Arc<[u8]>>,
+    pub(crate) options: RequestOptions,
+}
+
+impl PreparedRequest {
+    pub(crate) fn new(head: Vec<u8>, request: &Request<Vec<u8>>, options: &RequestOptions) -> Self {
+        Self {
+            head: head.into(),
+            method: request.method().clone(),
+            headers: request.headers().clone(),
+            body_length: request.body().len(),
+            bound_body: (options.body_checksum.is_some() || options.before_send.is_some()).then(|| request.body().as_slice().into()),
+            options: options.clone(),
+        }
+    }
+
+    // The serialized request line and headers
+    pub fn head(&self) -> &[u8] {
+        &self.head
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+}
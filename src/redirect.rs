@@ -0,0 +1,129 @@
+use http::Uri;
+use simple_error::SimpleResult;
+
+// A URI reference split into its RFC 3986 components, borrowed from the original string
+struct UriReference<'a> {
+    scheme: Option<&'a str>,
+    authority: Option<&'a str>,
+    path: &'a str,
+    query: Option<&'a str>,
+}
+
+impl<'a> UriReference<'a> {
+    fn parse(reference: &'a str) -> Self {
+        // Fragments stay on the client and can't be carried by http::Uri, so they are dropped
+        let reference = reference.split_once('#').map_or(reference, |(before, _)| before);
+        let (rest, query) = match reference.split_once('?') {
+            Some((rest, query)) => (rest, Some(query)),
+            None => (reference, None),
+        };
+
+        // A scheme is a letter followed by letters, digits, '+', '-' or '.', ending at the first ':' before any '/'
+        let (scheme, rest) = match rest.split_once(':') {
+            Some((scheme, after))
+                if !scheme.contains('/')
+                    && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && scheme.bytes().all(|b| b.is_ascii_alphanumeric() || b"+-.".contains(&b)) =>
+            {
+                (Some(scheme), after)
+            }
+            _ => (None, rest),
+        };
+
+        let (authority, path) = match rest.strip_prefix("//") {
+            Some(after) => {
+                let end = after.find('/').unwrap_or(after.len());
+                (Some(&after[..end]), &after[end..])
+            }
+            None => (None, rest),
+        };
+
+        Self { scheme, authority, path, query }
+    }
+}
+
+// Removes `.` and `..` segments from a path (RFC 3986 section 5.2.4)
+fn remove_dot_segments(path: &str) -> String {
+    let mut input = path.to_string();
+    let mut output = String::with_capacity(path.len());
+
+    // Drops the last segment written to the output, along with the '/' before it
+    fn pop_segment(output: &mut String) {
+        let end = output.rfind('/').unwrap_or(0);
+        output.truncate(end);
+    }
+
+    while !input.is_empty() {
+        if input.starts_with("../") {
+            input.drain(..3);
+        } else if input.starts_with("./") {
+            input.drain(..2);
+        } else if input.starts_with("/./") {
+            input.replace_range(..3, "/");
+        } else if input == "/." {
+            input = "/".to_string();
+        } else if input.starts_with("/../") {
+            input.replace_range(..4, "/");
+            pop_segment(&mut output);
+        } else if input == "/.." {
+            input = "/".to_string();
+            pop_segment(&mut output);
+        } else if input == "." || input == ".." {
+            input.clear();
+        } else {
+            // Move the first segment, with its leading '/' if any, to the output
+            let end = input[1..].find('/').map_or(input.len(), |index| index + 1);
+            output.extend(input.drain(..end));
+        }
+    }
+
+    output
+}
+
+// Merges a relative path with the base path (RFC 3986 section 5.2.3)
+fn merge_paths(base: &Uri, path: &str) -> String {
+    let base_path = base.path();
+    if base.authority().is_some() && base_path.is_empty() {
+        return format!("/{path}");
+    }
+    match base_path.rfind('/') {
+        Some(index) => format!("{}{path}", &base_path[..=index]),
+        None => path.to_string(),
+    }
+}
+
+// Resolves a Location header value against the URI of the request that was redirected, following RFC 3986
+// reference resolution: absolute URLs, scheme-relative `//host/path`, absolute paths, relative paths with
+// `.`/`..` segments and query-only references. Percent-encoding is kept as sent and fragments are dropped.
+pub fn resolve_redirect(base: &Uri, location: &str) -> SimpleResult<Uri> {
+    let base_scheme = base.scheme_str().ok_or("Redirect base URI has no scheme")?;
+    let base_authority = base.authority().ok_or("Redirect base URI has no authority")?.as_str();
+    let location = location.trim();
+    if location.is_empty() {
+        return Err("Empty Location header".into());
+    }
+
+    let reference = UriReference::parse(location);
+    let (scheme, authority, path, query) = if let Some(scheme) = reference.scheme {
+        let authority = reference.authority.ok_or_else(|| format!("Location {location:?} has no authority"))?;
+        (scheme, authority, remove_dot_segments(reference.path), reference.query)
+    } else if let Some(authority) = reference.authority {
+        (base_scheme, authority, remove_dot_segments(reference.path), reference.query)
+    } else if reference.path.is_empty() {
+        (base_scheme, base_authority, base.path().to_string(), reference.query.or(base.query()))
+    } else if reference.path.starts_with('/') {
+        (base_scheme, base_authority, remove_dot_segments(reference.path), reference.query)
+    } else {
+        (base_scheme, base_authority, remove_dot_segments(&merge_paths(base, reference.path)), reference.query)
+    };
+
+    if authority.is_empty() {
+        return Err(format!("Location {location:?} has an empty host").into());
+    }
+    let path = if path.is_empty() { "/" } else { path.as_str() };
+    let resolved = match query {
+        Some(query) => format!("{scheme}://{authority}{path}?{query}"),
+        None => format!("{scheme}://{authority}{path}"),
+    };
+    resolved.parse::<Uri>().map_err(|err| format!("Invalid redirect target {resolved:?}: {err}").into())
+}
@@ -1,10 +1,138 @@
-use http::{Request, Version};
+use std::{collections::HashMap, str::FromStr, time::SystemTime};
+
+use futures_lite::{AsyncWrite, AsyncWriteExt};
+use http::{header, HeaderMap, HeaderName, HeaderValue, Method, Request, Uri, Version};
 use simple_error::SimpleResult;
 
-// Serializes the HTTP request into a string format that can be sent over the network
-pub fn serialize_http_request<T>(req: &Request<T>) -> SimpleResult<String> {
+use crate::connect_options::ConnectOverrides;
+use crate::http_date;
+use crate::response::connection_has_token;
+use crate::request_options::RequestOptions;
+
+// Size of each chunk written when uploading with chunked transfer coding
+pub const UPLOAD_CHUNK_SIZE: usize = 16 * 1024;
+
+// Request extension recording the exact casing to write header names with, since HeaderName is always lowercase
+#[derive(Clone, Debug, Default)]
+pub struct HeaderCaseMap(HashMap<HeaderName, String>);
+
+impl HeaderCaseMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Records the casing of `name` as spelled by the caller
+    pub fn insert(&mut self, name: &str) -> SimpleResult<()> {
+        let header_name = HeaderName::from_str(name)?;
+        self.0.insert(header_name, name.to_string());
+        Ok(())
+    }
+
+    pub fn get(&self, name: &HeaderName) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+}
+
+// Inserts a header and opts it into preserve-case serialization
+pub fn insert_header_preserving_case<T>(req: &mut Request<T>, name: &str, value: HeaderValue) -> SimpleResult<()> {
+    let header_name = HeaderName::from_str(name)?;
+    req.headers_mut().insert(header_name, value);
+    let mut header_case_map = req.extensions_mut().remove::<HeaderCaseMap>().unwrap_or_default();
+    header_case_map.insert(name)?;
+    req.extensions_mut().insert(header_case_map);
+    Ok(())
+}
+
+// Host header value for the URI, omitting the port when it is the default for the scheme as browsers do
+pub fn host_header_value(uri: &Uri) -> Option<String> {
+    let host = uri.host()?;
+    let default_port = match uri.scheme_str() {
+        Some("http") | Some("ws") => Some(80),
+        Some("https") | Some("wss") => Some(443),
+        _ => None,
+    };
+    match uri.port_u16() {
+        Some(port) if Some(port) != default_port => Some(format!("{host}:{port}")),
+        _ => Some(host.to_string()),
+    }
+}
+
+// Whether `b` is a tchar, the characters allowed in a token such as the request method (RFC 9110)
+pub fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+// Whether requests with this method are expected to carry a body, so an empty one should be declared explicitly
+fn method_expects_body(method: &Method) -> bool {
+    method == Method::POST || method == Method::PUT || method == Method::PATCH
+}
+
+// Headers the caller didn't set but the request needs.
+// `body_length` is the length of the body written after the head, or None when it is framed by the caller.
+fn auto_headers<T>(req: &Request<T>, body_length: Option<usize>, options: &RequestOptions) -> Vec<(HeaderName, String)> {
+    let method = req.method();
+    let headers = req.headers();
+    let mut auto_headers: Vec<(HeaderName, String)> = Vec::new();
+    if !headers.contains_key(header::HOST) {
+        let host_override = req.extensions().get::<ConnectOverrides>().and_then(|overrides| overrides.host_header.as_ref());
+        let host = match host_override {
+            Some(host) => host.to_str().ok().map(str::to_string),
+            None => host_header_value(req.uri()),
+        };
+        if let Some(host) = host {
+            auto_headers.push((header::HOST, host));
+        }
+    }
+    let is_framed = headers.contains_key(header::CONTENT_LENGTH) || headers.contains_key(header::TRANSFER_ENCODING);
+    match body_length {
+        _ if is_framed => {}
+        // Without it some servers treat the next request on the connection as this one's body
+        Some(0) if method_expects_body(method) => auto_headers.push((header::CONTENT_LENGTH, "0".to_string())),
+        Some(0) | None => {}
+        // Any method, GET included, gets its body declared. Unusual, but some APIs (Elasticsearch) rely on it.
+        Some(body_length) => auto_headers.push((header::CONTENT_LENGTH, body_length.to_string())),
+    }
+    if options.send_date && !headers.contains_key(header::DATE) {
+        auto_headers.push((header::DATE, http_date::format_http_date(SystemTime::now())));
+    }
+    auto_headers
+}
+
+// Copies the request head with the automatic headers filled in, exactly as it would be serialized
+pub fn with_auto_headers<T>(req: &Request<T>, body_length: Option<usize>, options: &RequestOptions) -> SimpleResult<Request<()>> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in auto_headers(req, body_length, options) {
+        headers.insert(name, HeaderValue::from_str(&value)?);
+    }
+    for (name, value) in req.headers() {
+        headers.append(name, value.clone());
+    }
+
+    let mut head = Request::builder()
+        .method(req.method().clone())
+        .uri(req.uri().clone())
+        .version(req.version())
+        .body(())?;
+    *head.headers_mut() = headers;
+    if let Some(header_case_map) = req.extensions().get::<HeaderCaseMap>() {
+        head.extensions_mut().insert(header_case_map.clone());
+    }
+    if let Some(overrides) = req.extensions().get::<ConnectOverrides>() {
+        head.extensions_mut().insert(overrides.clone());
+    }
+    Ok(head)
+}
+
+// Serializes the HTTP request into a string format that can be sent over the network.
+// `body_length` is the length of the body written after the head, or None when it is framed by the caller.
+pub fn serialize_http_request<T>(req: &Request<T>, body_length: Option<usize>, options: &RequestOptions) -> SimpleResult<String> {
     let method = req.method();
+    // Extension methods can hold arbitrary bytes, which would allow injecting into the request line
+    if method.as_str().is_empty() || !method.as_str().bytes().all(is_tchar) {
+        return Err(format!("Invalid request method: {method:?}").into());
+    }
     let uri = req.uri();
+    let headers = req.headers();
 
     let path_and_query = uri.path_and_query().map_or("/", |pq| pq.as_str());
 
@@ -18,11 +146,123 @@ pub fn serialize_http_request<T>(req: &Request<T>) -> SimpleResult<String> {
 
     let mut request_line = format!("{method} {path_and_query} {version}\r\n");
 
-    for (name, value) in req.headers() {
-        request_line.push_str(&format!("{}: {}\r\n", name.as_str(), value.to_str()?));
+    let auto_headers = auto_headers(req, body_length, options);
+
+    // Header names go out lowercase unless the caller opted into preserving their casing
+    let header_case_map = req.extensions().get::<HeaderCaseMap>();
+    let wire_name = |name: &HeaderName| header_case_map.and_then(|map| map.get(name)).unwrap_or(name.as_str()).to_string();
+    for (name, value) in &auto_headers {
+        request_line.push_str(&format!("{}: {}\r\n", wire_name(name), value));
+    }
+    for (name, value) in headers {
+        request_line.push_str(&format!("{}: {}\r\n", wire_name(name), value.to_str()?));
     }
 
     request_line.push_str("\r\n");
 
     Ok(request_line)
 }
+
+// Whether the request asks the server to confirm with 100 Continue before the body is sent
+pub fn expects_continue(headers: &HeaderMap) -> bool {
+    headers.get(header::EXPECT).is_some_and(|expect| expect.as_bytes().eq_ignore_ascii_case(b"100-continue"))
+}
+
+// Asks the server to close the connection after responding, for requests sent on a connection that won't be
+// reused. This lets bodies framed by connection close end promptly. A caller that already chose close or keep-alive
+// wins; other Connection options, such as the TE that accept_trailers lists, get close appended.
+pub fn close_connection_by_default<T>(req: &mut Request<T>) {
+    let headers = req.headers();
+    if req.version() == Version::HTTP_11 && !connection_has_token(headers, "close") && !connection_has_token(headers, "keep-alive") {
+        req.headers_mut().append(header::CONNECTION, HeaderValue::from_static("close"));
+    }
+}
+
+// Advertises that the caller is willing to receive trailer fields after a chunked response body
+pub fn accept_trailers<T>(req: &mut Request<T>) {
+    let headers = req.headers_mut();
+    headers.insert(header::TE, HeaderValue::from_static("trailers"));
+    // TE is a hop-by-hop field, so it has to be listed in Connection as well
+    headers.append(header::CONNECTION, HeaderValue::from_static("TE"));
+}
+
+// Writes the body with chunked transfer coding, emitting the trailer fields after the last chunk
+pub async fn write_chunked_body<W>(writer: &mut W, body: &[u8], trailers: &HeaderMap<HeaderValue>) -> SimpleResult<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    for chunk in body.chunks(UPLOAD_CHUNK_SIZE) {
+        writer.write_all(format!("{:x}\r\n", chunk.len()).as_bytes()).await?;
+        writer.write_all(chunk).await?;
+        writer.write_all(b"\r\n").await?;
+    }
+
+    let mut last_chunk = String::from("0\r\n");
+    for (name, value) in trailers {
+        last_chunk.push_str(&format!("{}: {}\r\n", name.as_str(), value.to_str()?));
+    }
+    last_chunk.push_str("\r\n");
+    writer.write_all(last_chunk.as_bytes()).await?;
+    writer.flush().await?;
+
+    Ok(())
+}
+
+// Builds a quality-valued Accept header value, e.g. `application/json;q=1.0, text/plain;q=0.8`
+pub fn accept_list(media_ranges: &[(&str, f32)]) -> SimpleResult<HeaderValue> {
+    let mut accept = Vec::with_capacity(media_ranges.len());
+
+    for (media_range, quality) in media_ranges {
+        if !(0.0..=1.0).contains(quality) {
+            return Err(format!("Quality for {media_range} must be between 0 and 1").into());
+        }
+        // qvalues allow at most three decimal places
+        let quality = format!("{quality:.3}");
+        let quality = quality.trim_end_matches('0');
+        let quality = if quality.ends_with('.') { format!("{quality}0") } else { quality.to_string() };
+        accept.push(format!("{media_range};q={quality}"));
+    }
+
+    Ok(HeaderValue::from_str(&accept.join(", "))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host_of(uri: &str) -> Option<String> {
+        host_header_value(&uri.parse().unwrap())
+    }
+
+    #[test]
+    fn host_header_omits_default_https_port() {
+        assert_eq!(host_of("https://host:443/").as_deref(), Some("host"));
+    }
+
+    #[test]
+    fn host_header_keeps_other_ports() {
+        assert_eq!(host_of("https://host:8443/").as_deref(), Some("host:8443"));
+    }
+
+    #[test]
+    fn host_header_without_port() {
+        assert_eq!(host_of("http://host/").as_deref(), Some("host"));
+    }
+
+    #[test]
+    fn close_is_appended_after_accept_trailers() {
+        let mut req = Request::get("http://host/").body(()).unwrap();
+        accept_trailers(&mut req);
+        close_connection_by_default(&mut req);
+        let connection: Vec<_> = req.headers().get_all(header::CONNECTION).iter().collect();
+        assert_eq!(connection, ["TE", "close"]);
+    }
+
+    #[test]
+    fn caller_keep_alive_is_kept() {
+        let mut req = Request::get("http://host/").header(header::CONNECTION, "keep-alive").body(()).unwrap();
+        close_connection_by_default(&mut req);
+        let connection: Vec<_> = req.headers().get_all(header::CONNECTION).iter().collect();
+        assert_eq!(connection, ["keep-alive"]);
+    }
+}
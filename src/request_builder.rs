@@ -0,0 +1,57 @@
+use http::{request, HeaderName, HeaderValue, Method, Request, Response};
+use simple_error::SimpleResult;
+
+use crate::deadline::Deadline;
+use crate::HttpClient;
+
+// A request being put together for an HttpClient, started by `client.get(uri)` and friends.
+// Produces a plain http::Request and sends it through the client, so client defaults apply.
+pub struct RequestBuilder<'a> {
+    client: &'a HttpClient,
+    builder: request::Builder,
+    body: Vec<u8>,
+}
+
+impl<'a> RequestBuilder<'a> {
+    pub(crate) fn new(client: &'a HttpClient, method: Method, uri: &str) -> Self {
+        Self {
+            client,
+            builder: Request::builder().method(method).uri(uri),
+            body: Vec::new(),
+        }
+    }
+
+    // Adds a header. Invalid names or values are reported by `build` and `send`.
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        self.builder = self.builder.header(key, value);
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    // Fails the request with DeadlineExceeded if it isn't done by `deadline`, which may be shared with other requests
+    pub fn deadline(mut self, deadline: Deadline) -> Self {
+        self.builder = self.builder.extension(deadline);
+        self
+    }
+
+    // The request as it would be sent, before client defaults are applied
+    pub fn build(self) -> SimpleResult<Request<Vec<u8>>> {
+        Ok(self.builder.body(self.body)?)
+    }
+
+    pub async fn send(self) -> SimpleResult<Response<Vec<u8>>> {
+        let client = self.client;
+        let request = self.build()?;
+        client.send(request).await
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use http::Request;
+use simple_error::SimpleResult;
+
+use crate::checksum::BodyChecksum;
+use crate::clock::{Clock, SystemClock};
+
+// Default cap on a chunk-size line or trailer line
+pub const DEFAULT_MAX_CHUNK_LINE_LENGTH: usize = 1024;
+
+// Default cap on the response status line
+pub const DEFAULT_MAX_STATUS_LINE_LENGTH: usize = 8 * 1024;
+
+// Default cap on the whole response header section
+pub const DEFAULT_MAX_HEADER_SECTION_SIZE: usize = 64 * 1024;
+
+// Default cap on how many values one header name may collect in a response
+pub const DEFAULT_MAX_HEADER_VALUES_PER_NAME: usize = 100;
+
+// Last-chance hook over the request head, e.g. to sign it. It runs after Host, Content-Length and Date have been
+// filled in, so whatever it sees (and changes) is exactly what gets written.
+#[derive(Clone)]
+pub struct BeforeSend(pub Arc<BeforeSendHook>);
+
+type BeforeSendHook = dyn Fn(&mut Request<()>) -> SimpleResult<()> + Send + Sync;
+
+impl fmt::Debug for BeforeSend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BeforeSend")
+    }
+}
+
+// What to do with bytes the server sent after the end of a complete response. Only bytes that already arrived with
+// the response are seen; the connection isn't read further to look for more.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrailingDataPolicy {
+    // Discard them, which is fine when the connection is closed after one response
+    #[default]
+    Ignore,
+    // Fail the request, since a server that overruns its framing can't be trusted with the connection
+    Error,
+    // Keep them in a TrailingData response extension, e.g. the start of the next pipelined response
+    Preserve,
+}
+
+// Bytes that followed a complete response, kept under TrailingDataPolicy::Preserve
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrailingData(pub Vec<u8>);
+
+// Lowest throughput accepted while reading a response body. The time allowed grows with the declared length, so a
+// large download isn't cut off by a flat timeout and a small one doesn't get to dawdle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MinDownloadSpeed {
+    pub bytes_per_second: u64,
+    // Allowed on top of the transfer time itself, to absorb latency and a slow start
+    pub grace: Duration,
+}
+
+impl MinDownloadSpeed {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second,
+            grace: Duration::from_secs(5),
+        }
+    }
+
+    // How long `length` bytes may take to arrive at this speed
+    pub fn allowed(&self, length: u64) -> Duration {
+        let nanos = u128::from(length) * 1_000_000_000 / u128::from(self.bytes_per_second.max(1));
+        self.grace.saturating_add(Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX)))
+    }
+}
+
+// Knobs applied by HttpClient when writing a request and reading its response
+#[derive(Clone, Debug)]
+pub struct RequestOptions {
+    // Longest chunk-size or trailer line accepted in a chunked body, so a server can't stream an endless line
+    pub max_chunk_line_length: usize,
+    // Longest status line accepted, so a server that never sends a newline can't make the client buffer forever
+    pub max_status_line_length: usize,
+    // Sends the request head and body in a single write so a small request goes out as one segment.
+    // Requests carrying `Expect: 100-continue` always use separate writes since the body must wait.
+    pub coalesce_writes: bool,
+    // How long request_with_options holds back the body of an `Expect: 100-continue` request waiting for the server
+    // to answer. Zero keeps the header but sends the body right behind the head, saving a round trip against
+    // servers that never answer Expect at the cost of uploading bodies the server may reject; a 100 Continue sent
+    // while the body is still going out is skipped. The other request functions always send the body immediately.
+    pub expect_continue_timeout: Duration,
+    // For bodies with neither Content-Length nor chunked framing, stop reading once the server has been idle this
+    // long instead of waiting for it to close the connection. A heuristic for broken servers, off by default.
+    pub unframed_body_idle_timeout: Option<Duration>,
+    // Fails with SlowDownload when a Content-Length or chunked body arrives slower than this. Bodies framed by
+    // connection close have no declared length to measure against and use unframed_body_idle_timeout instead.
+    pub min_download_speed: Option<MinDownloadSpeed>,
+    pub before_send: Option<BeforeSend>,
+    // Attaches the request head as written to the response in a SentRequest extension, for debugging and checking
+    // signatures. Off by default since it keeps a copy of every head alive with its response.
+    pub retain_sent_request: bool,
+    // Adds a Date header with the current time to requests that lack one, for servers and signing schemes that
+    // require it
+    pub send_date: bool,
+    pub trailing_data: TrailingDataPolicy,
+    // Adds a digest of the body to requests that don't already carry the header. It is added before the
+    // before_send hook runs, so a signing hook can cover it.
+    pub body_checksum: Option<BodyChecksum>,
+    // Accepts chunked response bodies. Strict deployments can turn this off to fail with ChunkedNotAllowed and
+    // only ever deal with Content-Length framing.
+    pub allow_chunked: bool,
+    // Fails with UnframedBodyNotAllowed rather than reading a body that runs until the connection closes, since a
+    // truncated body of that kind can't be told from a complete one
+    pub require_framed_body: bool,
+    // Largest response header section accepted, across all its lines, before failing with HeadersTooLarge
+    pub max_header_section_size: usize,
+    // Most lines kept for any one header name, e.g. Set-Cookie. Repeats past it are dropped with a warning, or
+    // fail the response with TooManyHeaderValues when reject_excess_header_values is set.
+    pub max_header_values_per_name: usize,
+    pub reject_excess_header_values: bool,
+    // Times the deadline, min_download_speed, unframed_body_idle_timeout and expect_continue_timeout, so tests
+    // can drive them with a MockClock
+    pub clock: Arc<dyn Clock>,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self {
+            max_chunk_line_length: DEFAULT_MAX_CHUNK_LINE_LENGTH,
+            max_status_line_length: DEFAULT_MAX_STATUS_LINE_LENGTH,
+            coalesce_writes: false,
+            expect_continue_timeout: Duration::from_secs(1),
+            unframed_body_idle_timeout: None,
+            min_download_speed: None,
+            before_send: None,
+            retain_sent_request: false,
+            send_date: false,
+            trailing_data: TrailingDataPolicy::Ignore,
+            body_checksum: None,
+            allow_chunked: true,
+            require_framed_body: false,
+            max_header_section_size: DEFAULT_MAX_HEADER_SECTION_SIZE,
+            max_header_values_per_name: DEFAULT_MAX_HEADER_VALUES_PER_NAME,
+            reject_excess_header_values: false,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
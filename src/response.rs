@@ -1,17 +1,24 @@
-use std::str::FromStr;
+use std::future::Future;
+use std::time::{Duration, Instant};
 
-use futures_lite::{io::BufReader, AsyncBufReadExt, AsyncRead, AsyncReadExt};
-use http::{HeaderMap, HeaderName, HeaderValue, StatusCode, Version};
+use futures_lite::{future, io::BufReader, AsyncBufReadExt, AsyncRead, AsyncReadExt};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode, Version};
 use simple_error::{box_err, SimpleResult};
 
-// Reads the response status line from the stream
-pub async fn read_response_status_line<S>(reader: &mut BufReader<S>) -> SimpleResult<String>
+use crate::clock::Clock;
+use crate::error::HttpClientError;
+use crate::request_options::{MinDownloadSpeed, RequestOptions, DEFAULT_MAX_CHUNK_LINE_LENGTH, DEFAULT_MAX_HEADER_VALUES_PER_NAME};
+
+// Reads the response status line from the stream, failing with StatusLineTooLong past `max_length` bytes. It is
+// read as bytes, since the reason phrase may carry obs-text; only the version and status code need to be ASCII,
+// and any other bytes are replaced.
+pub async fn read_response_status_line<S>(reader: &mut BufReader<S>, max_length: usize) -> SimpleResult<String>
 where
     S: AsyncRead + Unpin,
 {
-    let mut response_status_line = String::new();
-    reader.read_line(&mut response_status_line).await?;
-    Ok(response_status_line)
+    let mut response_status_line = Vec::new();
+    read_line_capped(reader, &mut response_status_line, max_length).await?.ok_or(HttpClientError::StatusLineTooLong(max_length))?;
+    Ok(String::from_utf8_lossy(&response_status_line).into_owned())
 }
 
 // Parses the response status line into a version and status code
@@ -33,102 +40,627 @@ pub fn parse_response_status_line(response_status_line: &str) -> SimpleResult<(V
     Ok((response_version, response_status))
 }
 
-// Reads the response headers from the provided BufReader
-pub async fn read_response_headers<S>(reader: &mut BufReader<S>) -> SimpleResult<HeaderMap<HeaderValue>>
+// Parses a `name: value` header line into the map, splitting at the first colon. The line is raw bytes since values may carry obs-text
+// (0x80-0xFF), e.g. latin-1 in Server or Content-Disposition. Lines that don't have that shape, or whose name
+// isn't a token, are skipped so one bad field doesn't cost the whole response.
+// Once a name has `max_values_per_name` values, further lines for it are dropped, or fail the response when
+// `reject_excess` is set, so a flood of small repeated fields can't exhaust memory.
+fn parse_header_line(line: &[u8], headers: &mut HeaderMap<HeaderValue>, max_values_per_name: usize, reject_excess: bool) -> SimpleResult<()> {
+    let Some(index) = line.iter().position(|&b| b == b':') else {
+        log::warn!("Failed to parse header line: {}", String::from_utf8_lossy(line));
+        return Ok(());
+    };
+    let key = line[..index].to_ascii_lowercase();
+    // Whitespace around the value is optional (`Content-Length:42` is valid) and not part of it
+    let is_whitespace = |b: &u8| matches!(b, b' ' | b'\t' | b'\r' | b'\n');
+    let value = &line[index + 1..];
+    let value_start = value.iter().position(|b| !is_whitespace(b)).unwrap_or(value.len());
+    let value_end = value.iter().rposition(|b| !is_whitespace(b)).map_or(value_start, |index| index + 1);
+    match (HeaderName::from_bytes(&key), HeaderValue::from_bytes(&value[value_start..value_end])) {
+        // Repeated fields are kept in order, since lists like Transfer-Encoding may span several lines
+        (Ok(header_name), Ok(header_value)) => {
+            if headers.get_all(&header_name).iter().count() >= max_values_per_name {
+                if reject_excess {
+                    return Err(HttpClientError::TooManyHeaderValues(header_name.to_string()).into());
+                }
+                log::warn!("Dropping {header_name} header beyond the limit of {max_values_per_name} values");
+                return Ok(());
+            }
+            headers.append(header_name, header_value);
+        }
+        _ => log::warn!("Skipping invalid header line: {}", String::from_utf8_lossy(line)),
+    }
+    Ok(())
+}
+
+// Reads the response headers from the provided BufReader. The whole section, blank line included, may take at most
+// options.max_header_section_size bytes, so a server streaming one endless line can't exhaust memory.
+pub async fn read_response_headers<S>(reader: &mut BufReader<S>, options: &RequestOptions) -> SimpleResult<HeaderMap<HeaderValue>>
 where
     S: AsyncRead + Unpin,
 {
     let mut headers = HeaderMap::new();
-    let mut line = String::new();
-
-    while reader.read_line(&mut line).await? != 0 && line != "\r\n" {
-        if let Some((key, value)) = line.split_once(": ") {
-            let key = key.to_lowercase();
-            let value = value.trim_end_matches(|c: char| c == '\r' || c == '\n');
-            let header_name = HeaderName::from_str(&key)?;
-            let header_value = HeaderValue::from_str(value)?;
-            headers.insert(header_name, header_value);
-        } else {
-            log::warn!("Failed to parse header line: {line}");
+    let mut line = Vec::new();
+    let mut remaining = options.max_header_section_size;
+
+    loop {
+        let Some(read) = read_line_capped(reader, &mut line, remaining).await? else {
+            return Err(HttpClientError::HeadersTooLarge(options.max_header_section_size).into());
+        };
+        // EOF before the blank line, or a final line without its newline, means the headers were cut off
+        if read == 0 || !line.ends_with(b"\n") {
+            return Err(HttpClientError::UnexpectedEofInHeaders.into());
+        }
+        remaining -= read;
+        if line == b"\r\n" {
+            break;
         }
+        parse_header_line(&line, &mut headers, options.max_header_values_per_name, options.reject_excess_header_values)?;
         line.clear();
     }
 
     Ok(headers)
 }
 
-// Reads a chunked HTTP body from the provided BufReader
-pub async fn read_chunked_body<S>(reader: &mut BufReader<S>) -> SimpleResult<Vec<u8>>
+// Reads one line including its LF into `line`, giving up with None once it grows past `max_length` bytes.
+// Returns the number of bytes read, which is 0 at EOF.
+async fn read_line_capped<S>(reader: &mut BufReader<S>, line: &mut Vec<u8>, max_length: usize) -> SimpleResult<Option<usize>>
 where
     S: AsyncRead + Unpin,
 {
-    let mut body = Vec::new();
-    let mut chunk_size_line = String::new();
+    let start = line.len();
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(Some(line.len() - start));
+        }
 
+        let (used, found_newline) = match available.iter().position(|&b| b == b'\n') {
+            Some(index) => (index + 1, true),
+            None => (available.len(), false),
+        };
+        if line.len() - start + used > max_length {
+            return Ok(None);
+        }
+        line.extend_from_slice(&available[..used]);
+        reader.consume(used);
+
+        if found_newline {
+            return Ok(Some(line.len() - start));
+        }
+    }
+}
+
+// Reads one line including its LF into `line`, erroring once it grows past `max_length` bytes.
+// Returns the number of bytes read, which is 0 at EOF.
+pub async fn read_line_bounded<S>(reader: &mut BufReader<S>, line: &mut Vec<u8>, max_length: usize) -> SimpleResult<usize>
+where
+    S: AsyncRead + Unpin,
+{
+    read_line_capped(reader, line, max_length)
+        .await?
+        .ok_or_else(|| format!("Line exceeds maximum length of {max_length} bytes").into())
+}
+
+// Reads a chunk-size line and returns the size of the chunk that follows
+pub async fn read_chunk_size<S>(reader: &mut BufReader<S>, max_line_length: usize) -> SimpleResult<u64>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut chunk_size_line = Vec::new();
+    if read_line_bounded(reader, &mut chunk_size_line, max_line_length).await? == 0 {
+        return Err(box_err!("Unexpected EOF in chunked body"));
+    }
+    let chunk_size_line = std::str::from_utf8(&chunk_size_line)?;
+    Ok(u64::from_str_radix(chunk_size_line.trim(), 16)?)
+}
+
+// Reads the trailer section after the last chunk, which has the same shape as the header section
+pub async fn read_trailers<S>(reader: &mut BufReader<S>, max_line_length: usize) -> SimpleResult<HeaderMap<HeaderValue>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut trailers = HeaderMap::new();
+    let mut line = Vec::new();
+
+    while read_line_bounded(reader, &mut line, max_line_length).await? != 0 && line != b"\r\n" {
+        parse_header_line(&line, &mut trailers, DEFAULT_MAX_HEADER_VALUES_PER_NAME, false)?;
+        line.clear();
+    }
+
+    Ok(trailers)
+}
+
+// Parses the Content-Length header, if any. Surrounding whitespace is ignored and repeated identical
+// values (`42, 42` or several header lines) collapse into one, but conflicting values are rejected since
+// disagreeing lengths are a request smuggling vector.
+pub fn parse_content_length(headers: &HeaderMap<HeaderValue>) -> SimpleResult<Option<u64>> {
+    let mut content_length = None;
+
+    for header_value in headers.get_all("content-length") {
+        let header_value = header_value.to_str().map_err(|_| HttpClientError::InvalidContentLength(format!("{header_value:?}")))?;
+        for value in header_value.split(',') {
+            let value = value.trim();
+            // only plain digits; parse() would also accept a leading '+'
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(HttpClientError::InvalidContentLength(value.to_string()).into());
+            }
+            let parsed = value.parse::<u64>().map_err(|_| HttpClientError::InvalidContentLength(value.to_string()))?;
+            match content_length {
+                Some(existing) if existing != parsed => {
+                    return Err(HttpClientError::InvalidContentLength(format!("conflicting values {existing} and {parsed}")).into());
+                }
+                _ => content_length = Some(parsed),
+            }
+        }
+    }
+
+    Ok(content_length)
+}
+
+// Collects the transfer codings from every Transfer-Encoding line and comma list, in the order they were applied
+pub fn transfer_codings(headers: &HeaderMap<HeaderValue>) -> SimpleResult<Vec<String>> {
+    let mut codings = Vec::new();
+    for header_value in headers.get_all("transfer-encoding") {
+        for coding in header_value.to_str()?.split(',') {
+            let coding = coding.trim().to_ascii_lowercase();
+            // identity is a no-op left over from RFC 2616
+            if !coding.is_empty() && coding != "identity" {
+                codings.push(coding);
+            }
+        }
+    }
+    Ok(codings)
+}
+
+// Whether the body is framed by chunked transfer coding. Errors on codings this client can't undo, and on
+// chunked anywhere but last, which leaves the body length unknowable.
+pub fn is_chunked(headers: &HeaderMap<HeaderValue>) -> SimpleResult<bool> {
+    let codings = transfer_codings(headers)?;
+    let Some((last, rest)) = codings.split_last() else {
+        return Ok(false);
+    };
+    if last != "chunked" || rest.iter().any(|coding| coding == "chunked") {
+        return Err(format!("Invalid transfer coding list: {}", codings.join(", ")).into());
+    }
+    if let Some(coding) = rest.first() {
+        return Err(format!("Unsupported transfer coding: {coding}").into());
+    }
+    Ok(true)
+}
+
+// Whether the body length is declared by Content-Length or chunked framing rather than by connection close
+pub fn is_framed(headers: &HeaderMap<HeaderValue>) -> SimpleResult<bool> {
+    Ok(is_chunked(headers)? || parse_content_length(headers)?.is_some())
+}
+
+// Whether the Connection header lists `token`
+pub(crate) fn connection_has_token(headers: &HeaderMap<HeaderValue>, token: &str) -> bool {
+    headers
+        .get_all("connection")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+// Whether the connection stays open after this exchange. HTTP/1.1 is persistent unless either side sent
+// `Connection: close`, HTTP/1.0 only when the server answered with `Connection: keep-alive`.
+pub fn is_persistent(request_headers: &HeaderMap<HeaderValue>, response_version: Version, response_headers: &HeaderMap<HeaderValue>) -> bool {
+    if connection_has_token(request_headers, "close") || connection_has_token(response_headers, "close") {
+        return false;
+    }
+    match response_version {
+        Version::HTTP_10 => connection_has_token(response_headers, "keep-alive"),
+        _ => true,
+    }
+}
+
+// Whether the connection can carry another request after this exchange, e.g. before handing it back to a pool.
+// Both sides must have agreed to keep it open for their HTTP version, the response body must not have been framed
+// by the connection closing, and the request body must have been sent in full.
+pub fn connection_reusable<B, C>(request: &Request<B>, response: &Response<C>) -> bool {
+    if request.version() == Version::HTTP_10 && !connection_has_token(request.headers(), "keep-alive") {
+        return false;
+    }
+    if response.extensions().get::<BodyFraming>() == Some(&BodyFraming::UntilClose) || response.extensions().get::<UploadAborted>().is_some() {
+        return false;
+    }
+    is_persistent(request.headers(), response.version(), response.headers())
+}
+
+// Whether `status` is an interim response that precedes the final one. 101 counts as final.
+pub fn is_interim(status: StatusCode) -> bool {
+    status.is_informational() && status != StatusCode::SWITCHING_PROTOCOLS
+}
+
+// Looks at response bytes read while the request body was still being sent. Complete interim heads are dropped
+// from the front, and the result says whether a final status line has arrived. A status line that doesn't parse
+// counts as final, for the response reader to report.
+pub(crate) fn final_status_arrived(early: &mut Vec<u8>) -> bool {
     loop {
-        reader.read_line(&mut chunk_size_line).await?;
-        let chunk_size = usize::from_str_radix(chunk_size_line.trim(), 16)?;
+        let Some(line_end) = early.iter().position(|&byte| byte == b'\n') else {
+            return false;
+        };
+        let status = std::str::from_utf8(&early[..line_end])
+            .ok()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|code| StatusCode::from_bytes(code.as_bytes()).ok());
+        if !status.is_some_and(is_interim) {
+            return true;
+        }
+
+        // The interim head ends at its first empty line
+        let head_end = (line_end..early.len()).find_map(|i| match &early[i..] {
+            [b'\n', b'\r', b'\n', ..] => Some(i + 3),
+            [b'\n', b'\n', ..] => Some(i + 2),
+            _ => None,
+        });
+        let Some(head_end) = head_end else {
+            return false;
+        };
+        log::debug!("skipping interim response sent during the upload: {}", String::from_utf8_lossy(&early[..line_end]).trim_end());
+        early.drain(..head_end);
+    }
+}
+
+// Whether a response to `method` with `status` carries a body at all
+pub fn response_has_body(method: &Method, status: StatusCode) -> bool {
+    !(method == Method::HEAD || status.is_informational() || status == StatusCode::NO_CONTENT || status == StatusCode::NOT_MODIFIED)
+}
+
+// Reads a chunked HTTP body from the provided BufReader into `body`, returning the trailer section that follows it
+// With a minimum download speed, each chunk has to arrive within the time allowed for every byte declared so far.
+async fn read_chunked_body_into<S>(
+    reader: &mut BufReader<S>,
+    max_line_length: usize,
+    min_download_speed: Option<MinDownloadSpeed>,
+    clock: &dyn Clock,
+    body: &mut Vec<u8>,
+) -> SimpleResult<HeaderMap<HeaderValue>>
+where
+    S: AsyncRead + Unpin,
+{
+    let started = clock.now();
+    let mut declared = 0u64;
+    loop {
+        let chunk_size = within_download_speed(clock, min_download_speed, started, declared, read_chunk_size(reader, max_line_length)).await?;
+        let chunk_size = in_memory_length(chunk_size)?;
 
         if chunk_size == 0 {
             break;
         }
 
-        let mut chunk = vec![0; chunk_size];
-        reader.read_exact(&mut chunk).await?;
-        body.extend_from_slice(&chunk);
-
-        let mut crlf = [0; 2];
-        reader.read_exact(&mut crlf).await?;
-        if &crlf != b"\r\n" {
-            return Err(box_err!("Invalid chunked encoding: missing CRLF"));
+        declared = declared.saturating_add(chunk_size as u64);
+        let chunk_start = body.len();
+        let read_chunk = async {
+            read_exact_into(reader, chunk_size, body).await?;
+            let mut crlf = [0; 2];
+            reader.read_exact(&mut crlf).await?;
+            if &crlf != b"\r\n" {
+                return Err(box_err!("Invalid chunked encoding: missing CRLF"));
+            }
+            Ok(())
+        };
+        if let Err(err) = within_download_speed(clock, min_download_speed, started, declared, read_chunk).await {
+            body.truncate(chunk_start);
+            return Err(err);
         }
-        chunk_size_line.clear();
     }
 
-    Ok(body)
+    within_download_speed(clock, min_download_speed, started, declared, read_trailers(reader, max_line_length)).await
+}
+
+// Fails with SlowDownload unless `read` finishes within the time min_download_speed allows for `length` bytes,
+// counted from `started`, timed on `clock`
+async fn within_download_speed<T>(
+    clock: &dyn Clock,
+    min_download_speed: Option<MinDownloadSpeed>,
+    started: Instant,
+    length: u64,
+    read: impl Future<Output = SimpleResult<T>>,
+) -> SimpleResult<T> {
+    let Some(min_download_speed) = min_download_speed else {
+        return read.await;
+    };
+    let Some(deadline) = started.checked_add(min_download_speed.allowed(length)) else {
+        return read.await;
+    };
+    let remaining = deadline.saturating_duration_since(clock.now());
+    future::or(read, async move {
+        clock.sleep(remaining).await;
+        Err(HttpClientError::SlowDownload(min_download_speed.bytes_per_second).into())
+    })
+    .await
+}
+
+// Converts a declared length to usize for reading into memory. Lengths are u64 on the wire, so on 32-bit targets
+// a body may be too large to hold; those have to be read with BodyStream instead.
+fn in_memory_length(length: u64) -> SimpleResult<usize> {
+    usize::try_from(length).map_err(|_| HttpClientError::ResponseTooLargeForPlatform(length).into())
+}
+
+// Appends exactly `len` bytes from the reader to `body`
+async fn read_exact_into<S>(reader: &mut BufReader<S>, len: usize, body: &mut Vec<u8>) -> SimpleResult<()>
+where
+    S: AsyncRead + Unpin,
+{
+    let start = body.len();
+    let end = start.checked_add(len).ok_or(HttpClientError::ResponseTooLargeForPlatform((start as u64).saturating_add(len as u64)))?;
+    body.resize(end, 0);
+    if let Err(err) = reader.read_exact(&mut body[start..]).await {
+        body.truncate(start);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+// Drops trailer fields the server did not announce in its Trailer header
+pub fn filter_trailers(headers: &HeaderMap<HeaderValue>, trailers: HeaderMap<HeaderValue>) -> HeaderMap<HeaderValue> {
+    let announced: Vec<String> = match headers.get("trailer").and_then(|value| value.to_str().ok()) {
+        Some(value) => value.split(',').map(|name| name.trim().to_lowercase()).collect(),
+        None => return trailers, // nothing was negotiated, so there is nothing to validate against
+    };
+
+    let mut filtered = HeaderMap::new();
+    for (name, value) in trailers.iter() {
+        if announced.iter().any(|announced_name| announced_name == name.as_str()) {
+            filtered.append(name.clone(), value.clone());
+        } else {
+            log::warn!("Ignoring unannounced trailer: {name}");
+        }
+    }
+    filtered
 }
 
-// Reads the response body based on headers
+// Reads the response body based on headers, along with any trailers sent after a chunked body
 pub async fn read_response_body<S>(
     reader: &mut BufReader<S>,
     headers: &HeaderMap<HeaderValue>,
-) -> SimpleResult<Vec<u8>>
+    options: &RequestOptions,
+) -> SimpleResult<(Vec<u8>, BodyFraming, HeaderMap<HeaderValue>)>
 where
     S: AsyncRead + Unpin,
 {
-    if let Some(content_length_value) = headers.get("content-length") {
-        let content_length = content_length_value.to_str()?.parse::<usize>()?;
-        let mut response_body = vec![0u8; content_length];
-        reader.read_exact(&mut response_body).await?;
-        return Ok(response_body);
-    }  
-    
-    if let Some(transfer_encoding) = headers.get("transfer-encoding") {
-        if transfer_encoding == "chunked" {
-            return read_chunked_body(reader).await;
-        } else {
-            todo!()
-        }
-    }  
-    
-    if let Some(connection) = headers.get("connection") {
-        if connection == "upgrade" || connection == "Upgrade" {
-            return Ok(vec![]); // assume empty response body on websocket upgrade
-        } else if connection == "keep-alive" {
-            // do nothing?
-        } else if connection == "close" {
-            // do nothing?
-        } else {
-            todo!()
+    let mut body = Vec::new();
+    let (framing, trailers) = read_response_body_into(reader, headers, options, &mut body).await?;
+    Ok((body, framing, trailers))
+}
+
+// Appends the response body to a caller-owned buffer, so hot loops can reuse one allocation across responses.
+// Framing and limits are the same as read_response_body. Returns how the body was framed and any trailers
+// sent after a chunked body.
+pub async fn read_response_body_into<S>(
+    reader: &mut BufReader<S>,
+    headers: &HeaderMap<HeaderValue>,
+    options: &RequestOptions,
+    body: &mut Vec<u8>,
+) -> SimpleResult<(BodyFraming, HeaderMap<HeaderValue>)>
+where
+    S: AsyncRead + Unpin,
+{
+    // Transfer-Encoding overrides Content-Length when a server sends both
+    if is_chunked(headers)? {
+        if !options.allow_chunked {
+            return Err(HttpClientError::ChunkedNotAllowed.into());
         }
+        let trailers = read_chunked_body_into(reader, options.max_chunk_line_length, options.min_download_speed, &*options.clock, body).await?;
+        return Ok((BodyFraming::Chunked, filter_trailers(headers, trailers)));
     }
 
-    if let Some(content_length) = headers.get("content-length") {
-        let content_length = content_length.to_str()?.parse::<usize>()?;
-        let mut response_body = vec![0u8; content_length];
-        reader.read_exact(&mut response_body).await?;
-        return Ok(response_body);
+    if let Some(content_length) = parse_content_length(headers)? {
+        let body_start = body.len();
+        let read_body = read_exact_into(reader, in_memory_length(content_length)?, body);
+        if let Err(err) = within_download_speed(&*options.clock, options.min_download_speed, options.clock.now(), content_length, read_body).await {
+            body.truncate(body_start);
+            return Err(err);
+        }
+        return Ok((BodyFraming::ContentLength(content_length), HeaderMap::new()));
     }
 
-    todo!()
+    if connection_has_token(headers, "upgrade") {
+        return Ok((BodyFraming::None, HeaderMap::new())); // assume empty response body on websocket upgrade
+    }
+
+    // Neither a length nor chunked framing, so the body runs until the server closes the connection
+    if options.require_framed_body {
+        return Err(HttpClientError::UnframedBodyNotAllowed.into());
+    }
+    read_body_until_eof(reader, options.unframed_body_idle_timeout, &*options.clock, body).await?;
+    Ok((BodyFraming::UntilClose, HeaderMap::new()))
+}
+
+// Reads a body framed by connection close. With an idle timeout, gives up waiting once the server has been quiet
+// that long and keeps what arrived, for servers that neither frame the body nor close the connection.
+async fn read_body_until_eof<S>(reader: &mut BufReader<S>, idle_timeout: Option<Duration>, clock: &dyn Clock, body: &mut Vec<u8>) -> SimpleResult<()>
+where
+    S: AsyncRead + Unpin,
+{
+    let Some(idle_timeout) = idle_timeout else {
+        reader.read_to_end(body).await?;
+        return Ok(());
+    };
+
+    let start = body.len();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let read = future::or(async { Some(reader.read(&mut buffer).await) }, async {
+            clock.sleep(idle_timeout).await;
+            None
+        })
+        .await;
+
+        match read {
+            Some(Ok(0)) => break,
+            Some(Ok(len)) => body.extend_from_slice(&buffer[..len]),
+            Some(Err(err)) => return Err(err.into()),
+            None => {
+                log::warn!("Unframed response body idle for {idle_timeout:?}, assuming it ended after {} bytes", body.len() - start);
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Reads and throws away exactly `remaining` bytes using the scratch buffer
+async fn discard_exact<S>(reader: &mut BufReader<S>, mut remaining: u64, scratch: &mut [u8]) -> SimpleResult<()>
+where
+    S: AsyncRead + Unpin,
+{
+    while remaining > 0 {
+        let len = remaining.min(scratch.len() as u64) as usize;
+        reader.read_exact(&mut scratch[..len]).await?;
+        remaining -= len as u64;
+    }
+    Ok(())
+}
+
+// Consumes the framed response body without keeping it, so the connection can be reused.
+// Returns the number of body bytes discarded.
+pub async fn discard_response_body<S>(reader: &mut BufReader<S>, headers: &HeaderMap<HeaderValue>) -> SimpleResult<u64>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut scratch = [0u8; 8192];
+
+    if is_chunked(headers)? {
+        let mut discarded = 0;
+        loop {
+            let chunk_size = read_chunk_size(reader, DEFAULT_MAX_CHUNK_LINE_LENGTH).await?;
+            if chunk_size == 0 {
+                break;
+            }
+            // chunk data plus its trailing CRLF
+            discard_exact(reader, chunk_size + 2, &mut scratch).await?;
+            discarded += chunk_size;
+        }
+        read_trailers(reader, DEFAULT_MAX_CHUNK_LINE_LENGTH).await?;
+        return Ok(discarded);
+    }
+
+    if let Some(content_length) = parse_content_length(headers)? {
+        discard_exact(reader, content_length, &mut scratch).await?;
+        return Ok(content_length);
+    }
+
+    Err(box_err!("Cannot discard a body without Content-Length or chunked framing"))
+}
+
+// Consumes the response and decodes its body as UTF-8, reusing the body's allocation
+pub fn response_text(response: Response<Vec<u8>>) -> SimpleResult<String> {
+    Ok(String::from_utf8(response.into_body())?)
+}
+
+// Consumes the response and deserializes its JSON body
+pub fn response_json<T: miniserde::Deserialize>(response: Response<Vec<u8>>) -> SimpleResult<T> {
+    let text = response_text(response)?;
+    Ok(miniserde::json::from_str(&text)?)
+}
+
+// Response extension holding the trailer fields received after a chunked body
+#[derive(Clone, Debug, Default)]
+pub struct Trailers(pub HeaderMap<HeaderValue>);
+
+// Response extension holding the request head as it went on the wire, with automatic headers, the body checksum
+// and the before_send hook's changes applied. Only attached when RequestOptions::retain_sent_request is set.
+#[derive(Clone, Debug)]
+pub struct SentRequest(pub Request<()>);
+
+// Response extension marking a response that arrived before the request body was fully sent. The upload was cut
+// short, leaving the request's framing broken, so the connection can't carry another request.
+#[derive(Clone, Copy, Debug)]
+pub struct UploadAborted;
+
+// Response extension recording how the body was delimited on the wire
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyFraming {
+    ContentLength(u64),
+    Chunked,
+    // Read until the server closed the connection, which rules out reusing it
+    UntilClose,
+    // No body by definition, e.g. a 204, 304 or protocol upgrade
+    None,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_line_without_whitespace_is_parsed() {
+        let mut headers = HeaderMap::new();
+        parse_header_line(b"Content-Length:42\r\n", &mut headers, 100, false).unwrap();
+        parse_header_line(b"Transfer-Encoding: \tchunked \r\n", &mut headers, 100, false).unwrap();
+        parse_header_line(b"X-Empty:\r\n", &mut headers, 100, false).unwrap();
+        assert_eq!(headers.get("content-length").unwrap(), "42");
+        assert_eq!(headers.get("transfer-encoding").unwrap(), "chunked");
+        assert_eq!(headers.get("x-empty").unwrap(), "");
+    }
+
+    #[test]
+    fn header_value_keeps_later_colons() {
+        let mut headers = HeaderMap::new();
+        parse_header_line(b"Location: http://example.com:8080/\r\n", &mut headers, 100, false).unwrap();
+        assert_eq!(headers.get("location").unwrap(), "http://example.com:8080/");
+    }
+
+    #[test]
+    fn endless_header_line_is_refused() {
+        let mut head = b"X-Long: ".to_vec();
+        head.extend_from_slice(&[b'a'; 1024]);
+        let options = RequestOptions {
+            max_header_section_size: 512,
+            ..RequestOptions::default()
+        };
+        let mut reader = BufReader::new(futures_lite::io::Cursor::new(head));
+        let err = future::block_on(read_response_headers(&mut reader, &options)).unwrap_err();
+        assert!(matches!(err.downcast_ref::<HttpClientError>(), Some(HttpClientError::HeadersTooLarge(512))));
+    }
+
+    #[test]
+    fn interim_heads_sent_during_upload_are_skipped() {
+        let mut early = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 103 Early Hints\r\nLink: </a.css>\r\n\r\nHTTP/1.1 20".to_vec();
+        assert!(!final_status_arrived(&mut early));
+        assert_eq!(early, b"HTTP/1.1 20");
+        early.extend_from_slice(b"0 OK\r\n");
+        assert!(final_status_arrived(&mut early));
+        assert_eq!(early, b"HTTP/1.1 200 OK\r\n");
+    }
+
+    // Yields its bytes, then never returns anything more, like a server that neither frames nor closes
+    struct Stalled(futures_lite::io::Cursor<Vec<u8>>);
+
+    impl AsyncRead for Stalled {
+        fn poll_read(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>, buf: &mut [u8]) -> std::task::Poll<std::io::Result<usize>> {
+            match std::pin::Pin::new(&mut self.0).poll_read(cx, buf) {
+                std::task::Poll::Ready(Ok(0)) => std::task::Poll::Pending,
+                poll => poll,
+            }
+        }
+    }
+
+    #[test]
+    fn unframed_body_idle_timeout_runs_on_the_options_clock() {
+        let clock = std::sync::Arc::new(crate::clock::MockClock::new());
+        let idle_timeout = Duration::from_secs(30);
+        let options = RequestOptions {
+            unframed_body_idle_timeout: Some(idle_timeout),
+            clock: clock.clone(),
+            ..RequestOptions::default()
+        };
+        let mut reader = BufReader::new(Stalled(futures_lite::io::Cursor::new(b"partial".to_vec())));
+        let headers = HeaderMap::new();
+        let mut body = Vec::new();
+
+        let read = read_response_body_into(&mut reader, &headers, &options, &mut body);
+        let advance = async {
+            future::yield_now().await;
+            clock.advance(idle_timeout);
+        };
+        let (result, ()) = future::block_on(future::zip(read, advance));
+        assert_eq!(result.unwrap().0, BodyFraming::UntilClose);
+        assert_eq!(body, b"partial");
+    }
 }
@@ -0,0 +1,62 @@
+use http::{header, Response};
+
+// Only the start of the body is looked at, as in the WHATWG MIME sniffing standard
+const SNIFF_LENGTH: usize = 1445;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+const MAGIC_NUMBERS: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"\xfe\xff", "text/plain"),
+    (b"\xff\xfe", "text/plain"),
+];
+
+const HTML_PREFIXES: &[&[u8]] = &[b"<!doctype html", b"<html", b"<head", b"<body"];
+
+// Bytes that never appear in text, per the WHATWG binary data byte set
+fn is_binary_byte(b: u8) -> bool {
+    matches!(b, 0x00..=0x08 | 0x0b | 0x0e..=0x1a | 0x1c..=0x1f)
+}
+
+// Best-effort guess at the media type of a body whose declared Content-Type is missing or can't be trusted. A
+// conservative subset of WHATWG MIME sniffing: a few image and PDF signatures, HTML and XML by their opening tag,
+// JSON by a leading `{` or `[`, and otherwise text unless binary bytes show up. JSON detection in particular only
+// looks at the first character, so check the body parses before relying on it.
+pub fn sniff_content_type(body: &[u8]) -> &'static str {
+    let body = &body[..body.len().min(SNIFF_LENGTH)];
+    if let Some((_, media_type)) = MAGIC_NUMBERS.iter().find(|(magic, _)| body.starts_with(magic)) {
+        return media_type;
+    }
+
+    let text = body.strip_prefix(UTF8_BOM).unwrap_or(body);
+    let start = text.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(text.len());
+    let text = &text[start..];
+    let starts_with_ignore_case = |prefix: &[u8]| text.len() >= prefix.len() && text[..prefix.len()].eq_ignore_ascii_case(prefix);
+    if HTML_PREFIXES.iter().any(|prefix| starts_with_ignore_case(prefix)) {
+        return "text/html";
+    }
+    if starts_with_ignore_case(b"<?xml") {
+        return "text/xml";
+    }
+    if text.iter().any(|&b| is_binary_byte(b)) {
+        return "application/octet-stream";
+    }
+    if text.starts_with(b"{") || text.starts_with(b"[") {
+        return "application/json";
+    }
+    "text/plain"
+}
+
+// The response's declared media type, or a sniffed one when it declares none or only a generic one
+pub fn effective_content_type(response: &Response<Vec<u8>>) -> String {
+    let declared = response.headers().get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).unwrap_or("");
+    let media_type = declared.split(';').next().unwrap_or("").trim();
+    if media_type.is_empty() || media_type.eq_ignore_ascii_case("application/octet-stream") || media_type.eq_ignore_ascii_case("unknown/unknown") {
+        return sniff_content_type(response.body()).to_string();
+    }
+    media_type.to_ascii_lowercase()
+}
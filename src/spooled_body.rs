@@ -0,0 +1,171 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use futures_lite::{io::BufReader, AsyncRead, AsyncReadExt, AsyncWriteExt};
+use http::{HeaderMap, HeaderValue};
+use simple_error::{box_err, SimpleResult};
+
+use crate::body_stream::BodyStream;
+use crate::request_options::DEFAULT_MAX_CHUNK_LINE_LENGTH;
+use crate::response;
+
+// Size of the buffer body bytes are copied through
+const SPOOL_BUFFER_SIZE: usize = 64 * 1024;
+
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Temporary file holding a spilled body, removed when dropped
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    // Creates a fresh file for writing. The guard only exists once the file does, so a failed create never
+    // removes a file this process doesn't own.
+    async fn create() -> SimpleResult<(Self, async_fs::File)> {
+        let counter = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let file_name = format!("http_client-{}-{counter}.body", std::process::id());
+        let path = std::env::temp_dir().join(file_name);
+        let file = async_fs::OpenOptions::new().write(true).create_new(true).open(&path).await?;
+        Ok((Self { path }, file))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Opens the spilled body for reading
+    pub async fn open(&self) -> SimpleResult<async_fs::File> {
+        Ok(async_fs::File::open(&self.path).await?)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            log::warn!("Failed to remove {}: {err}", self.path.display());
+        }
+    }
+}
+
+// A response body kept in memory, or spilled to a temporary file once it grew past the threshold
+#[derive(Debug)]
+pub enum SpooledBody {
+    Memory(Vec<u8>),
+    File { file: TempFile, len: u64 },
+}
+
+impl SpooledBody {
+    pub fn len(&self) -> u64 {
+        match self {
+            SpooledBody::Memory(body) => body.len() as u64,
+            SpooledBody::File { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Collects body bytes, moving them to a temporary file once more than `threshold` bytes arrived
+struct Spooler {
+    threshold: usize,
+    memory: Vec<u8>,
+    file: Option<(TempFile, async_fs::File)>,
+    len: u64,
+}
+
+impl Spooler {
+    fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            memory: Vec::new(),
+            file: None,
+            len: 0,
+        }
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> SimpleResult<()> {
+        self.len += bytes.len() as u64;
+
+        if self.file.is_none() && self.memory.len() + bytes.len() > self.threshold {
+            let (temp_file, mut file) = TempFile::create().await?;
+            log::debug!("spilling response body to {}", temp_file.path().display());
+            file.write_all(&self.memory).await?;
+            self.memory = Vec::new();
+            self.file = Some((temp_file, file));
+        }
+
+        match &mut self.file {
+            Some((_, file)) => file.write_all(bytes).await?,
+            None => self.memory.extend_from_slice(bytes),
+        }
+
+        Ok(())
+    }
+
+    async fn finish(self) -> SimpleResult<SpooledBody> {
+        match self.file {
+            Some((temp_file, mut file)) => {
+                file.flush().await?;
+                Ok(SpooledBody::File { file: temp_file, len: self.len })
+            }
+            None => Ok(SpooledBody::Memory(self.memory)),
+        }
+    }
+}
+
+// Copies exactly `remaining` bytes from the reader into the spooler
+async fn spool_exact<S>(reader: &mut BufReader<S>, spooler: &mut Spooler, mut remaining: u64, buffer: &mut [u8]) -> SimpleResult<()>
+where
+    S: AsyncRead + Unpin,
+{
+    while remaining > 0 {
+        let len = remaining.min(buffer.len() as u64) as usize;
+        reader.read_exact(&mut buffer[..len]).await?;
+        spooler.write(&buffer[..len]).await?;
+        remaining -= len as u64;
+    }
+    Ok(())
+}
+
+// Reads the response body based on headers, spilling it to a temporary file past `spill_threshold` bytes
+pub async fn read_response_body_spooled<S>(reader: &mut BufReader<S>, headers: &HeaderMap<HeaderValue>, spill_threshold: usize) -> SimpleResult<SpooledBody>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut spooler = Spooler::new(spill_threshold);
+    let mut buffer = vec![0u8; SPOOL_BUFFER_SIZE];
+
+    if response::is_chunked(headers)? {
+        loop {
+            let chunk_size = response::read_chunk_size(reader, DEFAULT_MAX_CHUNK_LINE_LENGTH).await?;
+            if chunk_size == 0 {
+                break;
+            }
+            spool_exact(reader, &mut spooler, chunk_size, &mut buffer).await?;
+
+            let mut crlf = [0; 2];
+            reader.read_exact(&mut crlf).await?;
+            if &crlf != b"\r\n" {
+                return Err(box_err!("Invalid chunked encoding: missing CRLF"));
+            }
+        }
+        response::read_trailers(reader, DEFAULT_MAX_CHUNK_LINE_LENGTH).await?;
+        return spooler.finish().await;
+    }
+
+    if let Some(content_length) = response::parse_content_length(headers)? {
+        spool_exact(reader, &mut spooler, content_length, &mut buffer).await?;
+        return spooler.finish().await;
+    }
+
+    // Framed by connection close, so everything up to EOF is the body
+    let mut body = BodyStream::new(BufReader::new(reader), headers, DEFAULT_MAX_CHUNK_LINE_LENGTH)?;
+    while let Some(chunk) = body.next_chunk().await? {
+        spooler.write(&chunk).await?;
+    }
+    spooler.finish().await
+}
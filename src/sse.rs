@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+
+use futures_lite::{stream, AsyncRead, Stream};
+use http::{header, HeaderMap, HeaderValue};
+use simple_error::SimpleResult;
+
+use crate::body_stream::BodyStream;
+
+// A single Server-Sent Event. `id` is the last event ID in effect when it was dispatched.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Event {
+    pub event: Option<String>,
+    pub data: String,
+    pub id: Option<String>,
+    pub retry: Option<u64>,
+}
+
+// Whether the response carries an event stream
+pub fn is_event_stream(headers: &HeaderMap<HeaderValue>) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("text/event-stream"))
+}
+
+// Accumulates fields line by line, producing an event on each blank line (per the HTML event stream format)
+#[derive(Debug, Default)]
+pub struct EventParser {
+    event: Option<String>,
+    data: String,
+    last_event_id: Option<String>,
+    retry: Option<u64>,
+}
+
+impl EventParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Feeds one line without its line terminator, returning the event a blank line dispatches
+    pub fn feed_line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        // Lines starting with a colon are comments, often used as keep-alives
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => self.retry = value.parse().ok(),
+            _ => log::debug!("ignoring event stream field {field:?}"),
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let event = self.event.take();
+        let retry = self.retry.take();
+        // Events without data are dropped, but the event ID carries over
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut data = std::mem::take(&mut self.data);
+        data.pop();
+        Some(Event {
+            event,
+            data,
+            id: self.last_event_id.clone(),
+            retry,
+        })
+    }
+}
+
+// Decodes events from a response body as it arrives
+pub struct EventStream<S> {
+    body: BodyStream<S>,
+    parser: EventParser,
+    buffer: Vec<u8>,
+    // A CR ended the previous line, so a leading LF belongs to it
+    skip_lf: bool,
+    // Whether the first line has been fed, which is the only place a byte order mark is dropped
+    started: bool,
+    pending: VecDeque<Event>,
+}
+
+impl<S: AsyncRead + Unpin> EventStream<S> {
+    pub fn new(body: BodyStream<S>) -> Self {
+        Self {
+            body,
+            parser: EventParser::new(),
+            buffer: Vec::new(),
+            skip_lf: false,
+            started: false,
+            pending: VecDeque::new(),
+        }
+    }
+
+    // Returns the next event, or None once the server ends the stream
+    pub async fn next_event(&mut self) -> SimpleResult<Option<Event>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            // An incomplete trailing event is discarded when the stream ends
+            let Some(bytes) = self.body.next_chunk().await? else {
+                return Ok(None);
+            };
+            self.feed(&bytes);
+        }
+    }
+
+    // Splits the bytes into lines ending in CRLF, LF or CR and feeds the complete ones to the parser. As the
+    // event stream format requires, invalid UTF-8 is replaced rather than failing the stream, and one leading byte
+    // order mark is dropped.
+    fn feed(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            if std::mem::take(&mut self.skip_lf) && b == b'\n' {
+                continue;
+            }
+            if b == b'\r' || b == b'\n' {
+                self.skip_lf = b == b'\r';
+                let line = std::mem::take(&mut self.buffer);
+                let line = String::from_utf8_lossy(&line);
+                let line = if std::mem::replace(&mut self.started, true) { &*line } else { line.strip_prefix('\u{feff}').unwrap_or(&line) };
+                if let Some(event) = self.parser.feed_line(line) {
+                    self.pending.push_back(event);
+                }
+            } else {
+                self.buffer.push(b);
+            }
+        }
+    }
+
+    // Adapts the event stream into a futures Stream, which ends after the first error
+    pub fn into_stream(self) -> impl Stream<Item = SimpleResult<Event>> {
+        stream::unfold(Some(self), |state| async move {
+            let mut event_stream = state?;
+            match event_stream.next_event().await {
+                Ok(Some(event)) => Some((Ok(event), Some(event_stream))),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), None)),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::future;
+    use futures_lite::io::{BufReader, Cursor};
+
+    use super::*;
+    use crate::request_options::DEFAULT_MAX_CHUNK_LINE_LENGTH;
+
+    #[test]
+    fn bom_is_dropped_and_invalid_utf8_replaced() {
+        let wire = b"\xEF\xBB\xBFdata: caf\xFF\n\ndata: \xEF\xBB\xBFkept\n\n".to_vec();
+        let body = BodyStream::new(BufReader::new(Cursor::new(wire)), &HeaderMap::new(), DEFAULT_MAX_CHUNK_LINE_LENGTH).unwrap();
+        let mut events = EventStream::new(body);
+
+        assert_eq!(future::block_on(events.next_event()).unwrap().unwrap().data, "caf\u{fffd}");
+        assert_eq!(future::block_on(events.next_event()).unwrap().unwrap().data, "\u{feff}kept");
+        assert!(future::block_on(events.next_event()).unwrap().is_none());
+    }
+}
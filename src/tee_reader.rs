@@ -0,0 +1,31 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_lite::AsyncRead;
+
+// Copies every byte read from the inner reader into a buffer, capturing a response exactly as received
+pub struct TeeReader<R> {
+    inner: R,
+    captured: Vec<u8>,
+}
+
+impl<R> TeeReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, captured: Vec::new() }
+    }
+
+    pub fn into_captured(self) -> Vec<u8> {
+        self.captured
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for TeeReader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(read)) = &poll {
+            self.captured.extend_from_slice(&buf[..*read]);
+        }
+        poll
+    }
+}
@@ -0,0 +1,171 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rustls::{Certificate, ClientConfig, KeyLogFile, OwnedTrustAnchor, RootCertStore, SupportedProtocolVersion};
+use simple_error::SimpleResult;
+
+// TLS protocol versions rustls can negotiate, in ascending order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    Tls12,
+    Tls13,
+}
+
+impl TlsVersion {
+    fn supported_version(&self) -> &'static SupportedProtocolVersion {
+        match self {
+            TlsVersion::Tls12 => &rustls::version::TLS12,
+            TlsVersion::Tls13 => &rustls::version::TLS13,
+        }
+    }
+}
+
+// TLS settings turned into a rustls ClientConfig when the client is built
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    // Trust the bundled webpki roots
+    pub use_webpki_roots: bool,
+    // Directory of .pem/.crt files whose certificates are added to the root store
+    pub ca_dir: Option<PathBuf>,
+    // Fail when any file in `ca_dir` can't be loaded instead of skipping it with a warning
+    pub strict_ca_dir: bool,
+    // Write session secrets to the file named by SSLKEYLOGFILE so captures can be decrypted. Debugging only.
+    pub key_log: bool,
+    // Lowest and highest protocol versions to negotiate, rustls's defaults (1.2 and 1.3) when unset
+    pub min_tls_version: Option<TlsVersion>,
+    pub max_tls_version: Option<TlsVersion>,
+    // Check that the certificate is valid for the host. Turning it off still validates the chain against the
+    // root store, for internal services whose certificates have a missing or mismatched SAN.
+    #[cfg(feature = "dangerous")]
+    pub verify_hostname: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            use_webpki_roots: true,
+            ca_dir: None,
+            strict_ca_dir: false,
+            key_log: false,
+            min_tls_version: None,
+            max_tls_version: None,
+            #[cfg(feature = "dangerous")]
+            verify_hostname: true,
+        }
+    }
+}
+
+// Adds every certificate in a PEM file to the root store, returning how many were added
+fn load_pem_file(path: &Path, root_store: &mut RootCertStore) -> SimpleResult<usize> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader)?;
+    for cert in &certs {
+        root_store.add(&Certificate(cert.clone()))?;
+    }
+    Ok(certs.len())
+}
+
+// Adds the certificates of every .pem/.crt file in the directory to the root store
+fn load_ca_dir(ca_dir: &Path, strict: bool, root_store: &mut RootCertStore) -> SimpleResult<()> {
+    for entry in std::fs::read_dir(ca_dir)? {
+        let path = entry?.path();
+        let is_cert_file = path.extension().is_some_and(|extension| extension == "pem" || extension == "crt");
+        if !path.is_file() || !is_cert_file {
+            continue;
+        }
+
+        match load_pem_file(&path, root_store) {
+            Ok(0) if strict => return Err(format!("No certificates found in {}", path.display()).into()),
+            Ok(0) => log::warn!("Skipping {}: no certificates found", path.display()),
+            Ok(count) => log::debug!("loaded {count} certificates from {}", path.display()),
+            Err(err) if strict => return Err(format!("Failed to load {}: {err}", path.display()).into()),
+            Err(err) => log::warn!("Skipping {}: {err}", path.display()),
+        }
+    }
+    Ok(())
+}
+
+// Validates the chain like rustls normally does but accepts certificates that don't name the host. webpki checks
+// the chain before the name, so a name mismatch means everything else already passed.
+#[cfg(feature = "dangerous")]
+struct SkipHostnameVerifier(rustls::client::WebPkiVerifier);
+
+#[cfg(feature = "dangerous")]
+impl rustls::client::ServerCertVerifier for SkipHostnameVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &rustls::ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: std::time::SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        match self.0.verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now) {
+            Err(rustls::Error::InvalidCertificate(rustls::CertificateError::NotValidForName)) => {
+                log::warn!("Certificate is not valid for {server_name:?}, accepting it since hostname verification is off");
+                Ok(rustls::client::ServerCertVerified::assertion())
+            }
+            result => result,
+        }
+    }
+}
+
+impl TlsConfig {
+    // The protocol versions allowed by the configured bounds
+    fn protocol_versions(&self) -> SimpleResult<Vec<&'static SupportedProtocolVersion>> {
+        let versions: Vec<_> = [TlsVersion::Tls12, TlsVersion::Tls13]
+            .into_iter()
+            .filter(|version| self.min_tls_version.is_none_or(|min| *version >= min) && self.max_tls_version.is_none_or(|max| *version <= max))
+            .map(|version| version.supported_version())
+            .collect();
+        if versions.is_empty() {
+            return Err(format!("No TLS version between {:?} and {:?}", self.min_tls_version, self.max_tls_version).into());
+        }
+        Ok(versions)
+    }
+
+    pub fn build_client_config(&self) -> SimpleResult<Arc<ClientConfig>> {
+        let mut root_store = RootCertStore::empty();
+
+        if self.use_webpki_roots {
+            root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|trust_anchor| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(trust_anchor.subject, trust_anchor.spki, trust_anchor.name_constraints)
+            }));
+        }
+
+        if let Some(ca_dir) = &self.ca_dir {
+            load_ca_dir(ca_dir, self.strict_ca_dir, &mut root_store)?;
+        }
+
+        if root_store.is_empty() {
+            return Err("TLS root store is empty".into());
+        }
+
+        #[cfg(feature = "dangerous")]
+        let verifier_roots = root_store.clone();
+
+        let mut client_config = ClientConfig::builder()
+            .with_safe_default_cipher_suites()
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(&self.protocol_versions()?)?
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+
+        #[cfg(feature = "dangerous")]
+        if !self.verify_hostname {
+            log::warn!("TLS hostname verification disabled, certificates are only checked against the root store");
+            let verifier = SkipHostnameVerifier(rustls::client::WebPkiVerifier::new(verifier_roots, None));
+            client_config.dangerous().set_certificate_verifier(Arc::new(verifier));
+        }
+
+        if self.key_log {
+            log::warn!("TLS key logging enabled, session secrets will be written to SSLKEYLOGFILE");
+            client_config.key_log = Arc::new(KeyLogFile::new());
+        }
+
+        Ok(Arc::new(client_config))
+    }
+}
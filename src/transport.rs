@@ -0,0 +1,37 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use http::{Request, Response};
+use simple_error::SimpleResult;
+
+use crate::async_connection_factory::AsyncConnectionFactory;
+use crate::connect_options::ConnectOptions;
+use crate::error::{self, RequestStage};
+use crate::request_options::RequestOptions;
+use crate::HttpClient;
+
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = SimpleResult<Response<Vec<u8>>>> + Send + 'a>>;
+
+// Carries a request to a server and brings back its response. HttpClient dispatches through this,
+// so tests can swap the network for canned responses.
+pub trait Transport: Send + Sync {
+    fn roundtrip<'a>(&'a self, request: &'a Request<Vec<u8>>) -> TransportFuture<'a>;
+}
+
+// The real transport: opens a connection per request and speaks HTTP/1.1 over it
+#[derive(Clone, Default)]
+pub struct SocketTransport {
+    pub connect_options: ConnectOptions,
+    pub request_options: RequestOptions,
+}
+
+impl Transport for SocketTransport {
+    fn roundtrip<'a>(&'a self, request: &'a Request<Vec<u8>>) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let mut stream = error::with_stage(RequestStage::Connect, AsyncConnectionFactory::connect_with_options(request, &self.connect_options).await)?;
+            let response = HttpClient::request_with_options(&mut stream, request, &self.request_options).await;
+            HttpClient::close(&mut stream).await;
+            response
+        })
+    }
+}
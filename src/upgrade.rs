@@ -0,0 +1,43 @@
+use futures_lite::io::BufReader;
+use http::{header, HeaderValue, Request};
+use simple_error::SimpleResult;
+
+use crate::checksum::base64url;
+
+// A connection handed over to another protocol after a 101 Switching Protocols. `buffered` holds bytes the
+// server already sent past the response head, which belong to the new protocol and must be consumed first.
+pub struct Upgraded<S> {
+    pub stream: S,
+    pub buffered: Vec<u8>,
+}
+
+impl<S> Upgraded<S> {
+    // Takes the connection back from the reader used to parse the 101, keeping whatever it had buffered
+    pub fn from_reader(reader: BufReader<S>) -> Self {
+        let buffered = reader.buffer().to_vec();
+        Self {
+            stream: reader.into_inner(),
+            buffered,
+        }
+    }
+}
+
+// Asks the server to switch this request's connection to `protocol`
+pub fn request_upgrade<T>(req: &mut Request<T>, protocol: &str) -> SimpleResult<()> {
+    let headers = req.headers_mut();
+    headers.insert(header::UPGRADE, HeaderValue::from_str(protocol)?);
+    headers.append(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+    Ok(())
+}
+
+// Asks the server to switch to cleartext HTTP/2 (RFC 7540 section 3.2). `settings` is the payload of an HTTP/2
+// SETTINGS frame, which the server applies as if it had received it after the upgrade.
+pub fn request_h2c_upgrade<T>(req: &mut Request<T>, settings: &[u8]) -> SimpleResult<()> {
+    request_upgrade(req, "h2c")?;
+    let headers = req.headers_mut();
+    // HTTP2-Settings carries the payload as unpadded base64url
+    headers.insert("http2-settings", HeaderValue::from_str(&base64url(settings))?);
+    // HTTP2-Settings is hop-by-hop, so it has to be listed in Connection as well
+    headers.append(header::CONNECTION, HeaderValue::from_static("HTTP2-Settings"));
+    Ok(())
+}